@@ -1,4 +1,4 @@
-use std::fmt::{Display, Formatter, Result as FmtResult};
+use core::fmt::{Display, Formatter, Result as FmtResult};
 
 #[derive(Debug, Clone)]
 pub enum ShoobyError {
@@ -7,6 +7,15 @@ pub enum ShoobyError {
     InvalidTypeConversion,
     InvalidSize,
     InvalidType,
+    LayoutChanged,
+    CorruptedRecord,
+    StorageFailure,
+    /// No field has the given name
+    UnknownField,
+    /// Text or data that can't be parsed, like invalid JSON
+    InvalidFormat,
+    /// A new value was rejected by the interceptor, with the reason
+    Rejected(&'static str),
 }
 
 impl Display for ShoobyError {
@@ -17,8 +26,15 @@ impl Display for ShoobyError {
             ShoobyError::InvalidTypeConversion => write!(f, "Invalid type conversion"),
             ShoobyError::InvalidSize => write!(f, "Invalid size"),
             ShoobyError::InvalidType => write!(f, "Invalid type"),
+            ShoobyError::LayoutChanged => write!(f, "Field layout changed"),
+            ShoobyError::CorruptedRecord => write!(f, "Corrupted record"),
+            ShoobyError::StorageFailure => write!(f, "Storage failure"),
+            ShoobyError::UnknownField => write!(f, "Unknown field"),
+            ShoobyError::InvalidFormat => write!(f, "Invalid format"),
+            ShoobyError::Rejected(reason) => write!(f, "Rejected: {}", reason),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for ShoobyError {}
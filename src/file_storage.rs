@@ -0,0 +1,230 @@
+//! Storage of a whole DB in a single file, for Linux and host builds.
+//!
+//! File layout: | magic: u32 | schema version: u32 | entries...
+//! Entry layout: | name length: u8 | name | data length: u16 | data
+//! All numbers are little endian, a schema version of u32::MAX means none was saved.
+//! Fields are stored by name so reordering fields in shooby_db! keeps their values.
+//! Every save rewrites a temporary file and renames it over the old one, so the file is always complete.
+//! The fields of one DB write are saved as a batch with a single rename, so the file has all of them or none.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use crate::errors::ShoobyError;
+use crate::traits::ShoobyStorage;
+
+const FILE_MAGIC: u32 = 0x4642_4853; // "SHBF"
+const NO_SCHEMA_VERSION: u32 = u32::MAX;
+const FILE_HEADER_SIZE: usize = 8;
+
+#[derive(Default, Clone)]
+struct FileContent {
+    schema_version: Option<u32>,
+    fields: BTreeMap<String, Vec<u8>>,
+}
+
+/// ShoobyStorage keeping all the fields of a DB in one file.
+/// A missing, truncated or foreign file is not an error, the fields that can't be read get their defaults
+pub struct FileStorage<ID> {
+    path: PathBuf,
+    /// What is in the file, changed only after the file was written
+    content: Mutex<FileContent>,
+    /// Fields saved since begin_batch, written by commit_batch
+    batch: Mutex<Option<BTreeMap<String, Vec<u8>>>>,
+    _id: PhantomData<ID>,
+}
+
+impl<ID> FileStorage<ID> {
+    /// Opens the storage and reads the file at path if it exists
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let content = fs::read(&path)
+            .map(|bytes| Self::parse(&bytes))
+            .unwrap_or_default();
+
+        Self {
+            path,
+            content: Mutex::new(content),
+            batch: Mutex::new(None),
+            _id: PhantomData,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Parses as many complete entries as possible
+    fn parse(bytes: &[u8]) -> FileContent {
+        let mut content = FileContent::default();
+        if bytes.len() < FILE_HEADER_SIZE
+            || u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) != FILE_MAGIC
+        {
+            return content;
+        }
+
+        let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        if version != NO_SCHEMA_VERSION {
+            content.schema_version = Some(version);
+        }
+
+        let mut rest = &bytes[FILE_HEADER_SIZE..];
+        while let Some((name, data, next)) = Self::parse_entry(rest) {
+            content.fields.insert(name, data.to_vec());
+            rest = next;
+        }
+
+        content
+    }
+
+    fn parse_entry(bytes: &[u8]) -> Option<(String, &[u8], &[u8])> {
+        let (&name_len, rest) = bytes.split_first()?;
+        let name = rest.get(..name_len as usize)?;
+        let rest = &rest[name_len as usize..];
+        let data_len = u16::from_le_bytes([*rest.first()?, *rest.get(1)?]) as usize;
+        let data = rest.get(2..2 + data_len)?;
+        let name = core::str::from_utf8(name).ok()?;
+
+        Some((String::from(name), data, &rest[2 + data_len..]))
+    }
+
+    fn serialize(content: &FileContent) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&FILE_MAGIC.to_le_bytes());
+        bytes.extend_from_slice(
+            &content
+                .schema_version
+                .unwrap_or(NO_SCHEMA_VERSION)
+                .to_le_bytes(),
+        );
+
+        for (name, data) in content.fields.iter() {
+            bytes.push(name.len() as u8);
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(&(data.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(data);
+        }
+
+        bytes
+    }
+
+    fn temp_path(&self) -> PathBuf {
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        PathBuf::from(temp)
+    }
+
+    /// Applies change to a copy of the content and writes it, the content is replaced only if it was written
+    fn commit<F: FnOnce(&mut FileContent)>(&self, change: F) -> Result<(), ShoobyError> {
+        let mut content = self.content.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut updated = content.clone();
+        change(&mut updated);
+        self.flush(&updated)?;
+        *content = updated;
+        Ok(())
+    }
+
+    /// Writes everything to a temporary file and renames it over the file
+    fn flush(&self, content: &FileContent) -> Result<(), ShoobyError> {
+        let temp_path = self.temp_path();
+        let write = || -> std::io::Result<()> {
+            let mut file = fs::File::create(&temp_path)?;
+            file.write_all(&Self::serialize(content))?;
+            file.sync_all()?;
+            fs::rename(&temp_path, &self.path)?;
+
+            // the rename is durable only once the directory is synced
+            #[cfg(unix)]
+            {
+                let directory = match self.path.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => parent,
+                    _ => Path::new("."),
+                };
+                fs::File::open(directory)?.sync_all()?;
+            }
+            Ok(())
+        };
+
+        write().map_err(|_| ShoobyError::StorageFailure)
+    }
+}
+
+impl<ID: AsRef<str>> ShoobyStorage for FileStorage<ID> {
+    type ID = ID;
+
+    fn save_raw(&self, id: Self::ID, data: &[u8]) -> Result<(), ShoobyError> {
+        let name = id.as_ref();
+        if name.len() > u8::MAX as usize || data.len() > u16::MAX as usize {
+            return Err(ShoobyError::InvalidSize);
+        }
+
+        if let Some(batch) = self
+            .batch
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_mut()
+        {
+            batch.insert(String::from(name), data.to_vec());
+            return Ok(());
+        }
+
+        self.commit(|content| {
+            content.fields.insert(String::from(name), data.to_vec());
+        })
+    }
+
+    fn load_raw(&mut self, id: Self::ID, data: &mut [u8]) -> Result<bool, ShoobyError> {
+        match self
+            .content
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .fields
+            .get(id.as_ref())
+        {
+            Some(stored) if stored.len() != data.len() => Err(ShoobyError::InvalidSize),
+            Some(stored) => {
+                data.copy_from_slice(stored);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn save_schema_version(&self, version: u32) -> Result<(), ShoobyError> {
+        self.commit(|content| content.schema_version = Some(version))
+    }
+
+    fn load_schema_version(&mut self) -> Result<Option<u32>, ShoobyError> {
+        Ok(self
+            .content
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .schema_version)
+    }
+
+    fn begin_batch(&self) {
+        *self.batch.lock().unwrap_or_else(PoisonError::into_inner) = Some(BTreeMap::new());
+    }
+
+    fn commit_batch(&self) -> Result<(), ShoobyError> {
+        let batch = self
+            .batch
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        match batch {
+            Some(fields) if !fields.is_empty() => {
+                self.commit(|content| content.fields.extend(fields))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn abort_batch(&self) {
+        *self.batch.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+}
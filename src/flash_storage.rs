@@ -0,0 +1,629 @@
+//! Log structured storage over NOR flash.
+//!
+//! Field records are appended to the active sector, a newer record of a field replaces the older ones.
+//! When the active sector is full the log moves to the next free sector, so erases are spread over all sectors.
+//! One sector is always kept free: when the last free sector is taken, the latest records of the oldest
+//! sector are copied into it before its header is written, and only then the oldest sector is erased.
+//!
+//! Sector layout: | sequence: u32 | magic: u32 | records...
+//! The magic is programmed last, so a sector with a valid magic has a complete sequence.
+//! Record layout: | key: u16 | length: u16 | crc32 of key, length and data: u32 | data | padding
+//! All numbers are little endian, records are padded to the flash write size.
+//! A record cut by a power loss fails its crc and is ignored, the older record of the field is used instead.
+
+use core::cell::RefCell;
+use core::marker::PhantomData;
+
+use crate::errors::ShoobyError;
+use crate::traits::{ShoobyId, ShoobyStorage};
+use crate::utils::{crc32_update, CRC32_INIT};
+
+/// Minimal NOR flash interface needed by FlashStorage.
+/// Erased bytes read as 0xFF and a write can only clear bits.
+/// Offsets are relative to the start of the flash area given to the storage
+pub trait NorFlash {
+    /// Size of an erase sector in bytes
+    const SECTOR_SIZE: usize;
+    /// Number of sectors in the flash area, at least 2
+    const SECTOR_COUNT: usize;
+    /// Write offsets and lengths are multiples of it. Must be a power of two up to 16
+    const WRITE_SIZE: usize;
+
+    fn read(&mut self, offset: usize, bytes: &mut [u8]) -> Result<(), ShoobyError>;
+    fn write(&mut self, offset: usize, bytes: &[u8]) -> Result<(), ShoobyError>;
+    fn erase(&mut self, sector: usize) -> Result<(), ShoobyError>;
+}
+
+impl<F: NorFlash> NorFlash for &mut F {
+    const SECTOR_SIZE: usize = F::SECTOR_SIZE;
+    const SECTOR_COUNT: usize = F::SECTOR_COUNT;
+    const WRITE_SIZE: usize = F::WRITE_SIZE;
+
+    fn read(&mut self, offset: usize, bytes: &mut [u8]) -> Result<(), ShoobyError> {
+        (**self).read(offset, bytes)
+    }
+
+    fn write(&mut self, offset: usize, bytes: &[u8]) -> Result<(), ShoobyError> {
+        (**self).write(offset, bytes)
+    }
+
+    fn erase(&mut self, sector: usize) -> Result<(), ShoobyError> {
+        (**self).erase(sector)
+    }
+}
+
+const SECTOR_MAGIC: u32 = 0x5942_4853; // "SHBY"
+const SECTOR_HEADER_SIZE: usize = 8;
+const RECORD_HEADER_SIZE: usize = 8;
+const ERASED_KEY: u16 = 0xffff;
+const SCHEMA_VERSION_KEY: u16 = 0xfffe;
+// writes and reads are done through a small stack buffer
+const CHUNK_SIZE: usize = 16;
+// a rotation finds the latest record of every key with a table on the stack, so keys are limited
+const MAX_KEYS: usize = 256;
+
+/// ShoobyStorage over a NOR flash, see the module documentation for the format.
+/// The size of all the latest field records must fit in one sector, and the DB can have up to 256 fields
+pub struct FlashStorage<F: NorFlash, ID> {
+    log: RefCell<FlashLog<F>>,
+    _id: PhantomData<ID>,
+}
+
+impl<F: NorFlash, ID> FlashStorage<F, ID> {
+    /// Creates the storage and recovers its state from the flash, an erased flash is a valid empty storage
+    pub fn new(flash: F) -> Result<Self, ShoobyError> {
+        if F::SECTOR_COUNT < 2
+            || !F::WRITE_SIZE.is_power_of_two()
+            || F::WRITE_SIZE > CHUNK_SIZE
+            || !F::SECTOR_SIZE.is_multiple_of(CHUNK_SIZE)
+        {
+            return Err(ShoobyError::InvalidSize);
+        }
+
+        let mut log = FlashLog {
+            flash,
+            active: None,
+            write_offset: 0,
+        };
+        log.mount()?;
+
+        Ok(Self {
+            log: RefCell::new(log),
+            _id: PhantomData,
+        })
+    }
+
+    /// Direct access to the flash, writing through it may corrupt the storage
+    pub fn flash(&mut self) -> &mut F {
+        &mut self.log.get_mut().flash
+    }
+
+    /// Gives back the flash
+    pub fn release(self) -> F {
+        self.log.into_inner().flash
+    }
+}
+
+impl<F: NorFlash, ID: ShoobyId> ShoobyStorage for FlashStorage<F, ID> {
+    type ID = ID;
+
+    fn save_raw(&self, id: Self::ID, data: &[u8]) -> Result<(), ShoobyError> {
+        self.log.borrow_mut().append(id.index() as u16, data)
+    }
+
+    fn load_raw(&mut self, id: Self::ID, data: &mut [u8]) -> Result<bool, ShoobyError> {
+        self.log.get_mut().load(id.index() as u16, data)
+    }
+
+    fn save_schema_version(&self, version: u32) -> Result<(), ShoobyError> {
+        self.log
+            .borrow_mut()
+            .append(SCHEMA_VERSION_KEY, &version.to_le_bytes())
+    }
+
+    fn load_schema_version(&mut self) -> Result<Option<u32>, ShoobyError> {
+        let mut version = [0u8; 4];
+        let loaded = self.log.get_mut().load(SCHEMA_VERSION_KEY, &mut version)?;
+        Ok(loaded.then(|| u32::from_le_bytes(version)))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ActiveSector {
+    sector: usize,
+    sequence: u32,
+}
+
+/// What was found at an offset of a sector
+enum Entry {
+    Record {
+        key: u16,
+        data_offset: usize,
+        len: usize,
+        valid: bool,
+        next: usize,
+    },
+    /// Erased space, no more records in the sector
+    End,
+    /// A header that can't be walked over, the rest of the sector is unusable
+    Broken,
+}
+
+struct FlashLog<F: NorFlash> {
+    flash: F,
+    active: Option<ActiveSector>,
+    /// offset in the active sector for the next record
+    write_offset: usize,
+}
+
+impl<F: NorFlash> FlashLog<F> {
+    // ================= LAYOUT =================
+
+    fn align(len: usize) -> usize {
+        (len + F::WRITE_SIZE - 1) & !(F::WRITE_SIZE - 1)
+    }
+
+    fn sector_start(sector: usize) -> usize {
+        sector * F::SECTOR_SIZE
+    }
+
+    fn first_record_offset() -> usize {
+        Self::align(SECTOR_HEADER_SIZE)
+    }
+
+    fn sector_sequence(&mut self, sector: usize) -> Result<Option<u32>, ShoobyError> {
+        let mut header = [0u8; SECTOR_HEADER_SIZE];
+        self.flash.read(Self::sector_start(sector), &mut header)?;
+        let sequence = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let magic = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        Ok((magic == SECTOR_MAGIC).then_some(sequence))
+    }
+
+    fn free_sectors(&mut self) -> Result<usize, ShoobyError> {
+        let mut free = 0;
+        for sector in 0..F::SECTOR_COUNT {
+            if self.sector_sequence(sector)?.is_none() {
+                free += 1;
+            }
+        }
+        Ok(free)
+    }
+
+    /// The valid sector with the lowest sequence that is higher than after
+    fn next_sector_by_sequence(
+        &mut self,
+        after: Option<u32>,
+    ) -> Result<Option<ActiveSector>, ShoobyError> {
+        let mut found: Option<ActiveSector> = None;
+        for sector in 0..F::SECTOR_COUNT {
+            if let Some(sequence) = self.sector_sequence(sector)? {
+                let is_after = after.is_none_or(|after| sequence > after);
+                let is_lower = found.is_none_or(|found| sequence < found.sequence);
+                if is_after && is_lower {
+                    found = Some(ActiveSector { sector, sequence });
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    fn newest_sector(&mut self) -> Result<Option<ActiveSector>, ShoobyError> {
+        let mut newest: Option<ActiveSector> = None;
+        for sector in 0..F::SECTOR_COUNT {
+            if let Some(sequence) = self.sector_sequence(sector)? {
+                if newest.is_none_or(|newest| sequence > newest.sequence) {
+                    newest = Some(ActiveSector { sector, sequence });
+                }
+            }
+        }
+        Ok(newest)
+    }
+
+    fn read_entry(&mut self, sector: usize, offset: usize) -> Result<Entry, ShoobyError> {
+        if offset + RECORD_HEADER_SIZE > F::SECTOR_SIZE {
+            return Ok(Entry::End);
+        }
+
+        let mut header = [0u8; RECORD_HEADER_SIZE];
+        self.flash
+            .read(Self::sector_start(sector) + offset, &mut header)?;
+        if header.iter().all(|byte| *byte == 0xff) {
+            return Ok(Entry::End);
+        }
+
+        let key = u16::from_le_bytes([header[0], header[1]]);
+        let len = u16::from_le_bytes([header[2], header[3]]) as usize;
+        let crc = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let next = offset + Self::align(RECORD_HEADER_SIZE + len);
+        if key == ERASED_KEY || next > F::SECTOR_SIZE {
+            return Ok(Entry::Broken);
+        }
+
+        let data_offset = offset + RECORD_HEADER_SIZE;
+        let mut computed = crc32_update(CRC32_INIT, &header[..4]);
+        let mut chunk = [0u8; CHUNK_SIZE];
+        let mut done = 0;
+        while done < len {
+            let size = (len - done).min(CHUNK_SIZE);
+            self.flash.read(
+                Self::sector_start(sector) + data_offset + done,
+                &mut chunk[..size],
+            )?;
+            computed = crc32_update(computed, &chunk[..size]);
+            done += size;
+        }
+
+        Ok(Entry::Record {
+            key,
+            data_offset,
+            len,
+            valid: !computed == crc,
+            next,
+        })
+    }
+
+    fn is_erased(&mut self, sector: usize, mut offset: usize) -> Result<bool, ShoobyError> {
+        let mut chunk = [0u8; CHUNK_SIZE];
+        while offset < F::SECTOR_SIZE {
+            let size = (F::SECTOR_SIZE - offset).min(CHUNK_SIZE);
+            self.flash
+                .read(Self::sector_start(sector) + offset, &mut chunk[..size])?;
+            if chunk[..size].iter().any(|byte| *byte != 0xff) {
+                return Ok(false);
+            }
+            offset += size;
+        }
+        Ok(true)
+    }
+
+    /// Writes the concatenated parts at offset, padded to the write size
+    fn write_parts(&mut self, mut offset: usize, parts: &[&[u8]]) -> Result<(), ShoobyError> {
+        let mut chunk = [0xffu8; CHUNK_SIZE];
+        let mut filled = 0;
+        for part in parts {
+            for byte in part.iter() {
+                chunk[filled] = *byte;
+                filled += 1;
+                if filled == CHUNK_SIZE {
+                    self.flash.write(offset, &chunk)?;
+                    offset += CHUNK_SIZE;
+                    chunk = [0xff; CHUNK_SIZE];
+                    filled = 0;
+                }
+            }
+        }
+
+        if filled > 0 {
+            self.flash.write(offset, &chunk[..Self::align(filled)])?;
+        }
+        Ok(())
+    }
+
+    // ================= LOG =================
+
+    fn mount(&mut self) -> Result<(), ShoobyError> {
+        // the only way to have no free sector is a power loss right before the oldest sector was
+        // erased at the end of a rotation, its latest records were already copied
+        if self.free_sectors()? == 0 {
+            if let Some(oldest) = self.next_sector_by_sequence(None)? {
+                self.flash.erase(oldest.sector)?;
+            }
+        }
+
+        self.active = self.newest_sector()?;
+        self.write_offset = F::SECTOR_SIZE;
+
+        if let Some(active) = self.active {
+            let mut offset = Self::first_record_offset();
+            loop {
+                match self.read_entry(active.sector, offset)? {
+                    Entry::Record { next, .. } => offset = next,
+                    Entry::End => {
+                        // a record that was cut before its header was written may leave dirty bytes
+                        if self.is_erased(active.sector, offset)? {
+                            self.write_offset = offset;
+                        }
+                        break;
+                    }
+                    Entry::Broken => break,
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Finds the latest valid record of key, returns its sector, data offset and length
+    fn find_latest(&mut self, key: u16) -> Result<Option<(usize, usize, usize)>, ShoobyError> {
+        let mut latest = None;
+        let mut sequence = None;
+
+        while let Some(sector) = self.next_sector_by_sequence(sequence)? {
+            sequence = Some(sector.sequence);
+            let mut offset = Self::first_record_offset();
+            while let Entry::Record {
+                key: record_key,
+                data_offset,
+                len,
+                valid,
+                next,
+            } = self.read_entry(sector.sector, offset)?
+            {
+                if valid && record_key == key {
+                    latest = Some((sector.sector, data_offset, len));
+                }
+                offset = next;
+            }
+        }
+
+        Ok(latest)
+    }
+
+    fn load(&mut self, key: u16, data: &mut [u8]) -> Result<bool, ShoobyError> {
+        match self.find_latest(key)? {
+            Some((_, _, len)) if len != data.len() => Err(ShoobyError::InvalidSize),
+            Some((sector, data_offset, _)) => {
+                self.flash
+                    .read(Self::sector_start(sector) + data_offset, data)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn append(&mut self, key: u16, data: &[u8]) -> Result<(), ShoobyError> {
+        let size = Self::align(RECORD_HEADER_SIZE + data.len());
+        if key_slot(key).is_none()
+            || data.len() > u16::MAX as usize
+            || size > F::SECTOR_SIZE - Self::first_record_offset()
+        {
+            return Err(ShoobyError::InvalidSize);
+        }
+
+        if self.active.is_none() || self.write_offset + size > F::SECTOR_SIZE {
+            self.rotate()?;
+        }
+
+        if self.write_offset + size > F::SECTOR_SIZE {
+            return Err(ShoobyError::OutOfBounds);
+        }
+
+        let active = self.active.ok_or(ShoobyError::Unknown)?;
+        let len = (data.len() as u16).to_le_bytes();
+        let key_bytes = key.to_le_bytes();
+        let header = [key_bytes[0], key_bytes[1], len[0], len[1]];
+        let crc = !crc32_update(crc32_update(CRC32_INIT, &header), data);
+
+        let offset = Self::sector_start(active.sector) + self.write_offset;
+        // whatever happens the space is used, a failed write leaves dirty bytes behind
+        self.write_offset += size;
+        self.write_parts(offset, &[&header, &crc.to_le_bytes(), data])
+    }
+
+    /// Moves the log to the next free sector, compacting the oldest sector if it was the last free one
+    fn rotate(&mut self) -> Result<(), ShoobyError> {
+        let first = self.active.map_or(0, |active| active.sector + 1);
+        let mut next = None;
+        for i in 0..F::SECTOR_COUNT {
+            let sector = (first + i) % F::SECTOR_COUNT;
+            if self.sector_sequence(sector)?.is_none() {
+                next = Some(sector);
+                break;
+            }
+        }
+        let next = next.ok_or(ShoobyError::OutOfBounds)?;
+
+        let oldest = if self.free_sectors()? == 1 {
+            self.next_sector_by_sequence(None)?
+        } else {
+            None
+        };
+
+        // a free sector was erased when it was freed, unless a power loss cut a previous rotation
+        if !self.is_erased(next, 0)? {
+            self.flash.erase(next)?;
+        }
+        let mut offset = Self::first_record_offset();
+
+        // copy the latest records of the oldest sector, the new sector is not valid until its header is written
+        if let Some(oldest) = oldest {
+            let latest = self.latest_records_in(oldest)?;
+            let mut read_offset = Self::first_record_offset();
+            while let Entry::Record {
+                key,
+                valid,
+                next: next_record,
+                ..
+            } = self.read_entry(oldest.sector, read_offset)?
+            {
+                if valid && key_slot(key).map(|slot| latest[slot]) == Some(read_offset) {
+                    let size = next_record - read_offset;
+                    if offset + size > F::SECTOR_SIZE {
+                        return Err(ShoobyError::OutOfBounds);
+                    }
+                    self.copy(
+                        Self::sector_start(oldest.sector) + read_offset,
+                        Self::sector_start(next) + offset,
+                        size,
+                    )?;
+                    offset += size;
+                }
+                read_offset = next_record;
+            }
+        }
+
+        let sequence = self
+            .active
+            .map_or(1, |active| active.sequence.wrapping_add(1));
+        self.write_parts(
+            Self::sector_start(next),
+            &[&sequence.to_le_bytes(), &SECTOR_MAGIC.to_le_bytes()],
+        )?;
+        self.active = Some(ActiveSector {
+            sector: next,
+            sequence,
+        });
+        self.write_offset = offset;
+
+        if let Some(oldest) = oldest {
+            self.flash.erase(oldest.sector)?;
+        }
+
+        Ok(())
+    }
+
+    /// The offset of the latest valid record of every key slot that is in sector and not in a newer sector,
+    /// 0 for the others
+    fn latest_records_in(
+        &mut self,
+        sector: ActiveSector,
+    ) -> Result<[usize; MAX_KEYS + 1], ShoobyError> {
+        let mut latest = [0; MAX_KEYS + 1];
+        let mut current = Some(sector);
+
+        while let Some(walked) = current {
+            let mut offset = Self::first_record_offset();
+            while let Entry::Record {
+                key, valid, next, ..
+            } = self.read_entry(walked.sector, offset)?
+            {
+                if let Some(slot) = key_slot(key).filter(|_| valid) {
+                    latest[slot] = if walked.sector == sector.sector {
+                        offset
+                    } else {
+                        0
+                    };
+                }
+                offset = next;
+            }
+            current = self.next_sector_by_sequence(Some(walked.sequence))?;
+        }
+
+        Ok(latest)
+    }
+
+    fn copy(&mut self, from: usize, to: usize, size: usize) -> Result<(), ShoobyError> {
+        let mut chunk = [0u8; CHUNK_SIZE];
+        let mut done = 0;
+        while done < size {
+            let chunk_size = (size - done).min(CHUNK_SIZE);
+            self.flash.read(from + done, &mut chunk[..chunk_size])?;
+            self.flash.write(to + done, &chunk[..chunk_size])?;
+            done += chunk_size;
+        }
+        Ok(())
+    }
+}
+
+/// The slot of key in the table of latest records, None if the key is out of range
+fn key_slot(key: u16) -> Option<usize> {
+    match key {
+        SCHEMA_VERSION_KEY => Some(MAX_KEYS),
+        key if (key as usize) < MAX_KEYS => Some(key as usize),
+        _ => None,
+    }
+}
+
+/// NOR flash simulated in RAM for host tests, with a write size of 4 bytes.
+/// A power loss can be injected to stop programming after a number of bytes
+pub struct RamFlash<const SECTOR_SIZE: usize, const SECTOR_COUNT: usize> {
+    sectors: [[u8; SECTOR_SIZE]; SECTOR_COUNT],
+    erase_counts: [u32; SECTOR_COUNT],
+    power_budget: Option<usize>,
+}
+
+impl<const SECTOR_SIZE: usize, const SECTOR_COUNT: usize> RamFlash<SECTOR_SIZE, SECTOR_COUNT> {
+    /// A fully erased flash
+    pub fn new() -> Self {
+        Self {
+            sectors: [[0xff; SECTOR_SIZE]; SECTOR_COUNT],
+            erase_counts: [0; SECTOR_COUNT],
+            power_budget: None,
+        }
+    }
+
+    /// Only the next bytes written are programmed, then every write and erase fails until restore_power
+    pub fn cut_power_after(&mut self, bytes: usize) {
+        self.power_budget = Some(bytes);
+    }
+
+    pub fn restore_power(&mut self) {
+        self.power_budget = None;
+    }
+
+    /// The number of times a sector was erased
+    pub fn erase_count(&self, sector: usize) -> u32 {
+        self.erase_counts[sector]
+    }
+}
+
+impl<const SECTOR_SIZE: usize, const SECTOR_COUNT: usize> Default
+    for RamFlash<SECTOR_SIZE, SECTOR_COUNT>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SECTOR_SIZE: usize, const SECTOR_COUNT: usize> NorFlash
+    for RamFlash<SECTOR_SIZE, SECTOR_COUNT>
+{
+    const SECTOR_SIZE: usize = SECTOR_SIZE;
+    const SECTOR_COUNT: usize = SECTOR_COUNT;
+    const WRITE_SIZE: usize = 4;
+
+    fn read(&mut self, offset: usize, bytes: &mut [u8]) -> Result<(), ShoobyError> {
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            let address = offset + i;
+            *byte = *self
+                .sectors
+                .get(address / SECTOR_SIZE)
+                .ok_or(ShoobyError::OutOfBounds)?
+                .get(address % SECTOR_SIZE)
+                .ok_or(ShoobyError::OutOfBounds)?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, offset: usize, bytes: &[u8]) -> Result<(), ShoobyError> {
+        if !offset.is_multiple_of(Self::WRITE_SIZE) || !bytes.len().is_multiple_of(Self::WRITE_SIZE)
+        {
+            return Err(ShoobyError::InvalidSize);
+        }
+        if offset + bytes.len() > SECTOR_SIZE * SECTOR_COUNT {
+            return Err(ShoobyError::OutOfBounds);
+        }
+
+        let programmed = match self.power_budget {
+            Some(budget) => budget.min(bytes.len()),
+            None => bytes.len(),
+        };
+
+        for (i, byte) in bytes[..programmed].iter().enumerate() {
+            let address = offset + i;
+            // programming can only clear bits
+            self.sectors[address / SECTOR_SIZE][address % SECTOR_SIZE] &= *byte;
+        }
+
+        if let Some(budget) = self.power_budget.as_mut() {
+            *budget -= programmed;
+            if programmed < bytes.len() {
+                return Err(ShoobyError::StorageFailure);
+            }
+        }
+        Ok(())
+    }
+
+    fn erase(&mut self, sector: usize) -> Result<(), ShoobyError> {
+        if self.power_budget == Some(0) {
+            return Err(ShoobyError::StorageFailure);
+        }
+
+        self.sectors
+            .get_mut(sector)
+            .ok_or(ShoobyError::OutOfBounds)?
+            .fill(0xff);
+        self.erase_counts[sector] += 1;
+        Ok(())
+    }
+}
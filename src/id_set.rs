@@ -0,0 +1,105 @@
+use core::fmt::{Debug, Formatter, Result as FmtResult};
+use core::marker::PhantomData;
+
+use crate::traits::ShoobyId;
+
+/// A set of field IDs, one bit per field.
+/// shooby_db! generates the IdSet alias with enough words for its fields, and the const fn id_set
+/// to build one at compile time
+///     `const WIFI_FIELDS: NAME::IdSet = NAME::id_set(&[NAME::ID::SSID, NAME::ID::PASSWORD]);`
+pub struct ShoobyIdSet<ID, const WORDS: usize> {
+    words: [u32; WORDS],
+    _id: PhantomData<ID>,
+}
+
+impl<ID, const WORDS: usize> ShoobyIdSet<ID, WORDS> {
+    pub const fn empty() -> Self {
+        Self {
+            words: [0; WORDS],
+            _id: PhantomData,
+        }
+    }
+
+    /// Adds the field at index, used to build sets in const context.
+    /// Panics if the index does not fit in the set
+    pub const fn with_index(mut self, index: usize) -> Self {
+        self.words[index / 32] |= 1 << (index % 32);
+        self
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        let mut i = 0;
+        while i < WORDS {
+            if self.words[i] != 0 {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
+}
+
+impl<ID: ShoobyId, const WORDS: usize> ShoobyIdSet<ID, WORDS> {
+    /// A set with all the fields of the DB
+    pub const fn all() -> Self {
+        let mut set = Self::empty();
+        let mut index = 0;
+        while index < ID::FIELD_COUNT {
+            set = set.with_index(index);
+            index += 1;
+        }
+        set
+    }
+
+    pub fn insert(&mut self, id: ID) {
+        let index = id.index();
+        self.words[index / 32] |= 1 << (index % 32);
+    }
+
+    pub fn remove(&mut self, id: ID) {
+        let index = id.index();
+        self.words[index / 32] &= !(1 << (index % 32));
+    }
+
+    pub fn contains(&self, id: ID) -> bool {
+        let index = id.index();
+        self.words
+            .get(index / 32)
+            .is_some_and(|word| word & (1 << (index % 32)) != 0)
+    }
+
+    /// The IDs in the set, by field order
+    pub fn iter(&self) -> impl Iterator<Item = ID> + '_ {
+        (0..ID::FIELD_COUNT)
+            .filter_map(ID::from_index)
+            .filter(|id| self.contains(*id))
+    }
+}
+
+impl<ID, const WORDS: usize> Clone for ShoobyIdSet<ID, WORDS> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<ID, const WORDS: usize> Copy for ShoobyIdSet<ID, WORDS> {}
+
+impl<ID, const WORDS: usize> PartialEq for ShoobyIdSet<ID, WORDS> {
+    fn eq(&self, other: &Self) -> bool {
+        self.words == other.words
+    }
+}
+
+impl<ID, const WORDS: usize> Eq for ShoobyIdSet<ID, WORDS> {}
+
+impl<ID, const WORDS: usize> Default for ShoobyIdSet<ID, WORDS> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<ID: ShoobyId + Debug, const WORDS: usize> Debug for ShoobyIdSet<ID, WORDS> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
@@ -0,0 +1,374 @@
+//! JSON export and import of DB fields without heap.
+//!
+//! A DB is a JSON object of short field name to value, like `{"SSID":"MY_HOUSE","AUTO_CONNECT":true}`.
+//! Numbers and bools are JSON values, except I64 and U64 which are strings so readers that keep numbers
+//! as f64 don't round them past 2^53. Enums are the variant name and blobs are hex strings.
+//! Import also accepts qualified names and values in text, as ShoobyField::set_from_str reads them.
+
+use core::fmt::{Result as FmtResult, Write};
+
+use crate::errors::ShoobyError;
+use crate::shooby_field::{ShoobyField, ShoobyKind, ShoobyValue};
+use crate::traits::{ShoobyDb, ShoobyId};
+use crate::writer::FieldWriter;
+
+/// The longest JSON string with escapes that can be imported, it is decoded on the stack.
+/// Strings without escapes have no limit
+pub const JSON_ESCAPED_MAX_SIZE: usize = 256;
+
+/// The deepest array or object nesting in a value that is skipped on import
+const MAX_SKIP_DEPTH: u32 = u64::BITS;
+
+/// Writes all the fields as a JSON object
+///     `write_json(db.reader(), &mut uart)?;`
+pub fn write_json<ID: AsRef<str> + Copy, W: Write>(
+    fields: &[ShoobyField<ID>],
+    out: &mut W,
+) -> FmtResult {
+    write_object(fields.iter(), out)
+}
+
+/// Writes the fields of ids as a JSON object, by the order of ids
+///     `write_json_of(db.reader(), NETWORK_IDS.iter(), &mut uart)?;`
+pub fn write_json_of<ID: ShoobyId, W: Write>(
+    fields: &[ShoobyField<ID>],
+    ids: impl IntoIterator<Item = ID>,
+    out: &mut W,
+) -> FmtResult {
+    write_object(ids.into_iter().filter_map(|id| fields.get(id.index())), out)
+}
+
+fn write_object<'a, ID: AsRef<str> + Copy + 'static, W: Write>(
+    fields: impl Iterator<Item = &'a ShoobyField<ID>>,
+    out: &mut W,
+) -> FmtResult {
+    out.write_char('{')?;
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            out.write_char(',')?;
+        }
+        write_string(field.short_name(), out)?;
+        out.write_char(':')?;
+        write_value(&field.value(), out)?;
+    }
+    out.write_char('}')
+}
+
+fn write_value<W: Write>(value: &ShoobyValue<'_>, out: &mut W) -> FmtResult {
+    match *value {
+        // JSON has no NaN or infinity
+        ShoobyValue::Float(val) if !val.is_finite() => out.write_str("null"),
+        ShoobyValue::Enum(_, name) => write_string(name, out),
+        ShoobyValue::String(val) => write_string(val, out),
+        ShoobyValue::Blob(_) | ShoobyValue::I64(_) | ShoobyValue::U64(_) => {
+            write!(out, "\"{}\"", value)
+        }
+        _ => write!(out, "{}", value),
+    }
+}
+
+fn write_string<W: Write>(text: &str, out: &mut W) -> FmtResult {
+    out.write_char('"')?;
+    for c in text.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+/// Applies a JSON object of field names to values to the DB in one write, through the validating setters.
+/// Every field that is unknown or can't get its value is passed to on_error, and then nothing is changed
+/// and the error of the first of them is returned. JSON that can't be parsed returns InvalidFormat
+///     `apply_json(&mut db, r#"{"SSID":"x","AUTO_CONNECT":true}"#, |name, err| println!("{}: {}", name, err))?;`
+pub fn apply_json<DB, F>(db: &mut DB, json: &str, mut on_error: F) -> Result<(), ShoobyError>
+where
+    DB: ShoobyDb,
+    F: FnMut(&str, ShoobyError),
+{
+    db.try_write_with(|writer| {
+        let mut parser = Parser::new(json);
+        let mut first_error = None;
+        let mut reject = |name: &str, err: ShoobyError| {
+            on_error(name, err.clone());
+            first_error.get_or_insert(err);
+        };
+
+        parser.expect(b'{')?;
+        let mut more = !parser.next_is(b'}');
+        while more {
+            let mut name_buffer = [0u8; JSON_ESCAPED_MAX_SIZE];
+            let name = parser.string()?.decode(&mut name_buffer)?;
+            parser.expect(b':')?;
+            let value = parser.value()?;
+
+            match DB::ID::from_name(name) {
+                Some(id) => {
+                    let mut field = writer.field_mut(id);
+                    if let Err(err) = apply_value(&mut field, value) {
+                        reject(field.short_name(), err);
+                    }
+                }
+                None => reject(name, ShoobyError::UnknownField),
+            }
+
+            more = !parser.next_is(b'}');
+            if more {
+                parser.expect(b',')?;
+            }
+        }
+        parser.end()?;
+
+        first_error.map_or(Ok(()), Err)
+    })
+}
+
+fn apply_value<ID: AsRef<str> + Copy>(
+    field: &mut FieldWriter<'_, ID>,
+    value: JsonValue<'_>,
+) -> Result<(), ShoobyError> {
+    match (value, field.kind()) {
+        (JsonValue::Bool(val), ShoobyKind::Bool) => field.set_bool(val).map(|_| ()),
+        (JsonValue::Number(text), kind)
+            if kind != ShoobyKind::String && kind != ShoobyKind::Blob =>
+        {
+            field.set_from_str(text)
+        }
+        (JsonValue::String(text), _) => {
+            let mut buffer = [0u8; JSON_ESCAPED_MAX_SIZE];
+            field.set_from_str(text.decode(&mut buffer)?)
+        }
+        _ => Err(ShoobyError::InvalidType),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum JsonValue<'a> {
+    Bool(bool),
+    Number(&'a str),
+    String(JsonString<'a>),
+    /// null, an array or an object, no field takes them
+    Other,
+}
+
+/// The text of a JSON string between the quotes, escapes are not decoded yet
+#[derive(Clone, Copy)]
+struct JsonString<'a> {
+    raw: &'a str,
+    escaped: bool,
+}
+
+impl<'a> JsonString<'a> {
+    /// The string itself if it has no escapes, else decoded into buffer
+    fn decode<'b>(&self, buffer: &'b mut [u8]) -> Result<&'b str, ShoobyError>
+    where
+        'a: 'b,
+    {
+        if !self.escaped {
+            return Ok(self.raw);
+        }
+
+        let mut len = 0;
+        let mut chars = self.raw.chars();
+        while let Some(c) = chars.next() {
+            let c = match c {
+                '\\' => match chars.next() {
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('/') => '/',
+                    Some('b') => '\u{8}',
+                    Some('f') => '\u{c}',
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some('t') => '\t',
+                    Some('u') => decode_unicode(&mut chars)?,
+                    _ => return Err(ShoobyError::InvalidFormat),
+                },
+                c => c,
+            };
+
+            let encoded_len = c.len_utf8();
+            let out = buffer
+                .get_mut(len..len + encoded_len)
+                .ok_or(ShoobyError::InvalidSize)?;
+            c.encode_utf8(out);
+            len += encoded_len;
+        }
+
+        core::str::from_utf8(&buffer[..len]).map_err(|_| ShoobyError::InvalidFormat)
+    }
+}
+
+/// Decodes the digits of a \u escape, with the second half of a surrogate pair if needed
+fn decode_unicode(chars: &mut core::str::Chars<'_>) -> Result<char, ShoobyError> {
+    fn hex4(chars: &mut core::str::Chars<'_>) -> Result<u32, ShoobyError> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let digit = chars
+                .next()
+                .and_then(|c| c.to_digit(16))
+                .ok_or(ShoobyError::InvalidFormat)?;
+            value = value << 4 | digit;
+        }
+        Ok(value)
+    }
+
+    let high = hex4(chars)?;
+    let code = if (0xd800..0xdc00).contains(&high) {
+        if chars.next() != Some('\\') || chars.next() != Some('u') {
+            return Err(ShoobyError::InvalidFormat);
+        }
+        let low = hex4(chars)?;
+        if !(0xdc00..0xe000).contains(&low) {
+            return Err(ShoobyError::InvalidFormat);
+        }
+        0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+    } else {
+        high
+    };
+
+    char::from_u32(code).ok_or(ShoobyError::InvalidFormat)
+}
+
+/// Reads JSON tokens from text, any syntax error is InvalidFormat
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Self { text, pos: 0 }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        let bytes = self.text.as_bytes();
+        while self.pos < bytes.len() && bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        bytes.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), ShoobyError> {
+        match self.peek() {
+            Some(next) if next == byte => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(ShoobyError::InvalidFormat),
+        }
+    }
+
+    /// Consumes byte if it is next
+    fn next_is(&mut self, byte: u8) -> bool {
+        self.expect(byte).is_ok()
+    }
+
+    /// Only whitespace may follow the object
+    fn end(&mut self) -> Result<(), ShoobyError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(ShoobyError::InvalidFormat),
+        }
+    }
+
+    fn string(&mut self) -> Result<JsonString<'a>, ShoobyError> {
+        self.expect(b'"')?;
+        let bytes = self.text.as_bytes();
+        let start = self.pos;
+        let mut escaped = false;
+
+        loop {
+            match bytes.get(self.pos) {
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    escaped = true;
+                    self.pos += 2;
+                }
+                Some(byte) if *byte < 0x20 => return Err(ShoobyError::InvalidFormat),
+                Some(_) => self.pos += 1,
+                None => return Err(ShoobyError::InvalidFormat),
+            }
+        }
+
+        let raw = &self.text[start..self.pos];
+        self.pos += 1;
+        Ok(JsonString { raw, escaped })
+    }
+
+    fn value(&mut self) -> Result<JsonValue<'a>, ShoobyError> {
+        match self.peek().ok_or(ShoobyError::InvalidFormat)? {
+            b'"' => Ok(JsonValue::String(self.string()?)),
+            b't' => self.literal("true", JsonValue::Bool(true)),
+            b'f' => self.literal("false", JsonValue::Bool(false)),
+            b'n' => self.literal("null", JsonValue::Other),
+            b'-' | b'0'..=b'9' => self.number(),
+            b'[' | b'{' => {
+                self.skip_composite()?;
+                Ok(JsonValue::Other)
+            }
+            _ => Err(ShoobyError::InvalidFormat),
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: JsonValue<'a>) -> Result<JsonValue<'a>, ShoobyError> {
+        if self.text[self.pos..].starts_with(word) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            Err(ShoobyError::InvalidFormat)
+        }
+    }
+
+    fn number(&mut self) -> Result<JsonValue<'a>, ShoobyError> {
+        let start = self.pos;
+        let bytes = self.text.as_bytes();
+        while bytes
+            .get(self.pos)
+            .is_some_and(|byte| matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+        {
+            self.pos += 1;
+        }
+        Ok(JsonValue::Number(&self.text[start..self.pos]))
+    }
+
+    /// Skips an array or an object. The open ones are bits of a stack, set for an object, so it takes
+    /// no stack and every closer must match its opener. Nesting deeper than MAX_SKIP_DEPTH is InvalidFormat
+    fn skip_composite(&mut self) -> Result<(), ShoobyError> {
+        let mut objects = 0u64;
+        let mut depth = 0u32;
+        loop {
+            match self.peek().ok_or(ShoobyError::InvalidFormat)? {
+                opener @ (b'[' | b'{') => {
+                    if depth == MAX_SKIP_DEPTH {
+                        return Err(ShoobyError::InvalidFormat);
+                    }
+                    objects = objects << 1 | (opener == b'{') as u64;
+                    depth += 1;
+                    self.pos += 1;
+                }
+                closer @ (b']' | b'}') => {
+                    if (objects & 1 == 1) != (closer == b'}') {
+                        return Err(ShoobyError::InvalidFormat);
+                    }
+                    objects >>= 1;
+                    depth -= 1;
+                    self.pos += 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                b',' | b':' => self.pos += 1,
+                _ => {
+                    self.value()?;
+                }
+            }
+        }
+    }
+}
@@ -1,20 +1,54 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
 pub mod errors;
+#[cfg(feature = "std")]
+pub mod file_storage;
+pub mod flash_storage;
+pub mod id_set;
+pub mod json;
 pub mod multi_observers;
+#[cfg(feature = "serde")]
+pub mod serde_support;
+#[cfg(any(feature = "std", feature = "critical-section"))]
+pub mod shared_db;
+pub mod shell;
 pub mod shooby_field;
+pub mod snapshot;
 pub mod traits;
 mod utils;
+pub mod writer;
 
 #[macro_use]
 mod shooby_db_macro;
 
+// used by shooby_db! to name the typed accessors
+#[doc(hidden)]
+pub use paste;
+
 pub(crate) use shooby_field::*;
+// used by shooby_db!
+pub use shooby_field::{FieldMeta, LoadStatus, ShoobyChange, ShoobyKind, ShoobyRange, ShoobyValue};
 
 pub use errors::*;
-pub use multi_observers::MultiObserver;
+#[cfg(feature = "std")]
+pub use file_storage::FileStorage;
+pub use flash_storage::{FlashStorage, NorFlash, RamFlash};
+pub use id_set::ShoobyIdSet;
+pub use json::{apply_json, write_json, write_json_of};
+pub use multi_observers::{
+    DynMultiObserver, FnMultiObserver, MultiObserver, ObserverHandle, ShoobyCallback,
+};
+#[cfg(feature = "serde")]
+pub use serde_support::{apply_map, ShoobyMap};
+#[cfg(any(feature = "std", feature = "critical-section"))]
+pub use shared_db::{SharedDB, SharedReader};
+pub use shell::ShoobyShell;
+pub use snapshot::SNAPSHOT_FORMAT_VERSION;
 pub use traits::*;
+pub use writer::{FieldWriter, ShoobyWriter};
 
 #[derive(Debug, Copy, Clone)]
-#[repr(packed)]
+#[repr(C, packed)]
 #[allow(dead_code)]
 struct A {
     a: u32,
@@ -23,8 +57,36 @@ struct A {
 
 #[cfg(test)]
 mod tests {
-    #![allow(unaligned_references)]
+    #![allow(clippy::bool_assert_comparison)]
+    extern crate std;
     use super::*;
+    use std::string::{String, ToString};
+    use std::vec;
+    use std::vec::Vec;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Level {
+        Error = 1,
+        Info = 3,
+        Debug = 4,
+    }
+
+    impl ShoobyEnum for Level {
+        const VARIANTS: &'static [(u32, &'static str)] = &[(1, "Error"), (3, "Info"), (4, "Debug")];
+
+        fn to_discriminant(&self) -> u32 {
+            *self as u32
+        }
+
+        fn from_discriminant(discriminant: u32) -> Option<Self> {
+            match discriminant {
+                1 => Some(Level::Error),
+                3 => Some(Level::Info),
+                4 => Some(Level::Debug),
+                _ => None,
+            }
+        }
+    }
 
     macro_rules! create_db_instance {
         ($name:ident) => {
@@ -32,11 +94,67 @@ mod tests {
                 {NUM, Int, 15, Some((10, 100)), NON_PERSISTENT},
                 {STRING, String, "default", 24, NON_PERSISTENT},
                 {BOOLEAN, Bool, false, None, PERSISTENT},
-                {BLOB, Blob, A {a: 5, b: 9} , std::mem::size_of::<A>(), PERSISTENT},
+                {BLOB, Blob, A {a: 5, b: 9} , core::mem::size_of::<A>(), PERSISTENT},
+                {FLOAT, Float, 0.5, Some((-1.0, 1.0)), PERSISTENT},
+                {MASK, U32, 0xFFFF_0000, None, PERSISTENT},
+                {OFFSET, I64, -5, Some((-10_000_000_000, 10_000_000_000)), NON_PERSISTENT},
+                {UPTIME, U64, 0, None, PERSISTENT},
+                {LEVEL, Enum(Level), Level::Info, None, PERSISTENT},
             );
         };
     }
 
+    type MemStorageData =
+        std::rc::Rc<core::cell::RefCell<std::collections::HashMap<String, Vec<u8>>>>;
+
+    /// MemStorage keeps the schema version with the default save_schema_version, under the name of FIELD_NUM
+    const SCHEMA_VERSION_KEY: &str = "TESTER::ID::FIELD_NUM";
+
+    /// RAM storage keyed by the field name, so data survives a "reboot" into a new DB instance
+    struct MemStorage<ID> {
+        data: MemStorageData,
+        _id: core::marker::PhantomData<ID>,
+    }
+
+    impl<ID> MemStorage<ID> {
+        fn new(data: &MemStorageData) -> Self {
+            Self {
+                data: data.clone(),
+                _id: core::marker::PhantomData,
+            }
+        }
+    }
+
+    impl<ID: AsRef<str>> ShoobyStorage for MemStorage<ID> {
+        type ID = ID;
+
+        fn save_raw(&self, id: Self::ID, data: &[u8]) -> Result<(), ShoobyError> {
+            self.data
+                .borrow_mut()
+                .insert(id.as_ref().to_string(), data.to_vec());
+            Ok(())
+        }
+
+        fn load_raw(&mut self, id: Self::ID, data: &mut [u8]) -> Result<bool, ShoobyError> {
+            match self.data.borrow().get(id.as_ref()) {
+                Some(stored) if stored.len() == data.len() => {
+                    data.copy_from_slice(stored);
+                    Ok(true)
+                }
+                Some(_) => Err(ShoobyError::InvalidSize),
+                None => Ok(false),
+            }
+        }
+    }
+
+    /// a persisted record of field with the given value bytes
+    fn record<ID: AsRef<str> + Copy>(field: &ShoobyField<ID>, value: &[u8]) -> Vec<u8> {
+        let mut record = vec![0; RECORD_HEADER_SIZE];
+        record.extend_from_slice(value);
+        field.seal_record(&mut record);
+        record
+    }
+
     #[test]
     fn it_works() {
         create_db_instance!(TESTER);
@@ -45,16 +163,16 @@ mod tests {
         assert_eq!(reader[TESTER::ID::NUM].get_int::<f64>().unwrap(), 15.0);
         assert_eq!(reader[TESTER::ID::STRING].get_string().unwrap(), "default");
         assert_eq!(reader[TESTER::ID::BOOLEAN].get_bool().unwrap(), false);
-        assert_eq!(reader[TESTER::ID::BLOB].get_blob::<A>().unwrap().a, 5);
-        assert_eq!(reader[TESTER::ID::BLOB].get_blob::<A>().unwrap().b, 9);
+        assert_eq!({ reader[TESTER::ID::BLOB].get_blob::<A>().unwrap().a }, 5);
+        assert_eq!({ reader[TESTER::ID::BLOB].get_blob::<A>().unwrap().b }, 9);
     }
 
     #[test]
     #[should_panic]
     fn it_panics() {
         create_db_instance!(TESTER);
-        let db_1 = TESTER::take_db_with_empty_observer_and_storage();
-        let db_2 = TESTER::take_db_with_empty_observer_and_storage();
+        let _db_1 = TESTER::take_db_with_empty_observer_and_storage();
+        let _db_2 = TESTER::take_db_with_empty_observer_and_storage();
     }
 
     #[test]
@@ -63,12 +181,26 @@ mod tests {
         let mut db = TESTER::take_db_with_empty_observer_and_storage();
 
         db.write_with(|writer| {
-            assert_eq!(writer[TESTER::ID::NUM].set_num::<i8>(17i8).unwrap(), 15);
-            assert_eq!(writer[TESTER::ID::BOOLEAN].set_bool(true).unwrap(), false);
-            writer[TESTER::ID::STRING]
+            assert_eq!(
+                writer
+                    .field_mut(TESTER::ID::NUM)
+                    .set_num::<i8>(17i8)
+                    .unwrap(),
+                15
+            );
+            assert_eq!(
+                writer
+                    .field_mut(TESTER::ID::BOOLEAN)
+                    .set_bool(true)
+                    .unwrap(),
+                false
+            );
+            writer
+                .field_mut(TESTER::ID::STRING)
                 .set_string("I LOVE JENNY")
                 .unwrap();
-            writer[TESTER::ID::BLOB]
+            writer
+                .field_mut(TESTER::ID::BLOB)
                 .set_blob(&A { a: 80, b: 90 })
                 .unwrap();
         });
@@ -80,14 +212,14 @@ mod tests {
             "I LOVE JENNY"
         );
         assert_eq!(reader[TESTER::ID::BOOLEAN].get_bool().unwrap(), true);
-        assert_eq!(reader[TESTER::ID::BLOB].get_blob::<A>().unwrap().a, 80);
-        assert_eq!(reader[TESTER::ID::BLOB].get_blob::<A>().unwrap().b, 90);
+        assert_eq!({ reader[TESTER::ID::BLOB].get_blob::<A>().unwrap().a }, 80);
+        assert_eq!({ reader[TESTER::ID::BLOB].get_blob::<A>().unwrap().b }, 90);
     }
 
     #[test]
     fn name_as_str() {
         create_db_instance!(TESTER);
-        let mut db = TESTER::take_db_with_empty_observer_and_storage();
+        let db = TESTER::take_db_with_empty_observer_and_storage();
 
         assert_eq!(db.name(), "TESTER");
         assert_eq!(db.reader()[TESTER::ID::NUM].name(), "TESTER::ID::NUM");
@@ -125,7 +257,7 @@ mod tests {
         let mut db: TESTER::DB<TestObserver> = TESTER::take_with_observer_only(Some(observer));
 
         db.write_with(|writer| {
-            writer[TESTER::ID::NUM].set_num(90).unwrap();
+            writer.field_mut(TESTER::ID::NUM).set_num(90).unwrap();
         });
 
         assert_eq!(boolcell.get(), true);
@@ -169,7 +301,7 @@ mod tests {
             TESTER::take_with_observer_only(Some(multi_observer));
 
         db.write_with(|writer| {
-            writer[TESTER::ID::NUM].set_num(90).unwrap();
+            writer.field_mut(TESTER::ID::NUM).set_num(90).unwrap();
         });
 
         assert_eq!(bools[0].get(), true);
@@ -219,7 +351,7 @@ mod tests {
         });
 
         db.write_with(|writer| {
-            writer[TESTER::ID::NUM].set_num(90).unwrap();
+            writer.field_mut(TESTER::ID::NUM).set_num(90).unwrap();
         });
 
         assert_eq!(bools[0].get(), true);
@@ -234,12 +366,26 @@ mod tests {
 
         //changing all values
         db.write_with(|writer| {
-            assert_eq!(writer[TESTER::ID::NUM].set_num::<i8>(17i8).unwrap(), 15);
-            assert_eq!(writer[TESTER::ID::BOOLEAN].set_bool(true).unwrap(), false);
-            writer[TESTER::ID::STRING]
+            assert_eq!(
+                writer
+                    .field_mut(TESTER::ID::NUM)
+                    .set_num::<i8>(17i8)
+                    .unwrap(),
+                15
+            );
+            assert_eq!(
+                writer
+                    .field_mut(TESTER::ID::BOOLEAN)
+                    .set_bool(true)
+                    .unwrap(),
+                false
+            );
+            writer
+                .field_mut(TESTER::ID::STRING)
                 .set_string("I LOVE JENNY")
                 .unwrap();
-            writer[TESTER::ID::BLOB]
+            writer
+                .field_mut(TESTER::ID::BLOB)
                 .set_blob(&A { a: 80, b: 90 })
                 .unwrap();
         });
@@ -250,7 +396,2249 @@ mod tests {
         assert_eq!(reader[TESTER::ID::NUM].get_int::<i8>().unwrap(), 15);
         assert_eq!(reader[TESTER::ID::STRING].get_string().unwrap(), "default");
         assert_eq!(reader[TESTER::ID::BOOLEAN].get_bool().unwrap(), false);
-        assert_eq!(reader[TESTER::ID::BLOB].get_blob::<A>().unwrap().a, 5);
-        assert_eq!(reader[TESTER::ID::BLOB].get_blob::<A>().unwrap().b, 9);
+        assert_eq!({ reader[TESTER::ID::BLOB].get_blob::<A>().unwrap().a }, 5);
+        assert_eq!({ reader[TESTER::ID::BLOB].get_blob::<A>().unwrap().b }, 9);
+        // nothing is left to notify by the next write
+        assert!(reader.iter().all(|field| !field.has_changed));
+    }
+
+    #[test]
+    fn init_loads_persistent_fields() {
+        let data = MemStorageData::default();
+
+        {
+            create_db_instance!(TESTER);
+            let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)));
+            db.init().unwrap();
+            db.write_with(|writer| {
+                writer.field_mut(TESTER::ID::NUM).set_num(50).unwrap();
+                writer
+                    .field_mut(TESTER::ID::BOOLEAN)
+                    .set_bool(true)
+                    .unwrap();
+                writer
+                    .field_mut(TESTER::ID::BLOB)
+                    .set_blob(&A { a: 80, b: 90 })
+                    .unwrap();
+            });
+        }
+
+        // reboot
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)));
+        let report = db.init().unwrap();
+
+        assert_eq!(report[TESTER::ID::NUM], LoadStatus::Default);
+        assert_eq!(report[TESTER::ID::STRING], LoadStatus::Default);
+        assert_eq!(report[TESTER::ID::BOOLEAN], LoadStatus::Loaded);
+        assert_eq!(report[TESTER::ID::BLOB], LoadStatus::Loaded);
+
+        let reader = db.reader();
+        assert_eq!(reader[TESTER::ID::NUM].get_int::<i32>().unwrap(), 15);
+        assert_eq!(reader[TESTER::ID::BOOLEAN].get_bool().unwrap(), true);
+        assert_eq!({ reader[TESTER::ID::BLOB].get_blob::<A>().unwrap().a }, 80);
+        assert_eq!({ reader[TESTER::ID::BLOB].get_blob::<A>().unwrap().b }, 90);
+    }
+
+    #[test]
+    fn init_falls_back_to_default_on_corrupted_field() {
+        let data = MemStorageData::default();
+
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)));
+        let boolean = record(&db.reader()[TESTER::ID::BOOLEAN], &[7]);
+        data.borrow_mut()
+            .insert("TESTER::ID::BOOLEAN".to_string(), boolean);
+        data.borrow_mut()
+            .insert("TESTER::ID::BLOB".to_string(), vec![1, 2, 3]);
+
+        let report = db.init().unwrap();
+
+        assert_eq!(report[TESTER::ID::BOOLEAN], LoadStatus::Corrupted);
+        assert_eq!(report[TESTER::ID::BLOB], LoadStatus::LayoutChanged);
+
+        let reader = db.reader();
+        assert_eq!(reader[TESTER::ID::BOOLEAN].get_bool().unwrap(), false);
+        assert_eq!({ reader[TESTER::ID::BLOB].get_blob::<A>().unwrap().a }, 5);
+    }
+
+    #[test]
+    fn float_field() {
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_db_with_empty_observer_and_storage();
+        assert_eq!(
+            db.reader()[TESTER::ID::FLOAT].get_float::<f32>().unwrap(),
+            0.5
+        );
+
+        db.write_with(|writer| {
+            let mut float = writer.field_mut(TESTER::ID::FLOAT);
+            assert_eq!(float.set_float(-0.25).unwrap(), 0.5);
+            assert!(matches!(
+                float.set_float(1.5),
+                Err(ShoobyError::OutOfBounds)
+            ));
+            assert!(matches!(
+                float.set_float(f32::NAN),
+                Err(ShoobyError::OutOfBounds)
+            ));
+            assert!(matches!(
+                float.get_int::<i32>(),
+                Err(ShoobyError::InvalidType)
+            ));
+        });
+
+        let reader = db.reader();
+        assert_eq!(reader[TESTER::ID::FLOAT].get_float::<f64>().unwrap(), -0.25);
+        assert_eq!(
+            std::format!("{}", ShoobyFieldType::Float(-0.25)),
+            "Float(-0.25)"
+        );
+    }
+
+    #[test]
+    fn float_field_is_persisted() {
+        let data = MemStorageData::default();
+
+        {
+            create_db_instance!(TESTER);
+            let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)));
+            db.init().unwrap();
+            db.write_with(|writer| {
+                writer.field_mut(TESTER::ID::FLOAT).set_float(0.75).unwrap();
+            });
+        }
+
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)));
+        let report = db.init().unwrap();
+        assert_eq!(report[TESTER::ID::FLOAT], LoadStatus::Loaded);
+        assert_eq!(
+            db.reader()[TESTER::ID::FLOAT].get_float::<f32>().unwrap(),
+            0.75
+        );
+    }
+
+    #[test]
+    fn wide_integer_fields() {
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_db_with_empty_observer_and_storage();
+        let reader = db.reader();
+        assert_eq!(
+            reader[TESTER::ID::MASK].get_u32::<u32>().unwrap(),
+            0xFFFF_0000
+        );
+        assert_eq!(reader[TESTER::ID::OFFSET].get_i64::<i64>().unwrap(), -5);
+        assert_eq!(reader[TESTER::ID::UPTIME].get_u64::<u64>().unwrap(), 0);
+        assert!(matches!(
+            reader[TESTER::ID::MASK].get_u32::<i32>(),
+            Err(ShoobyError::InvalidTypeConversion)
+        ));
+        assert!(matches!(
+            reader[TESTER::ID::MASK].get_int::<i32>(),
+            Err(ShoobyError::InvalidType)
+        ));
+
+        db.write_with(|writer| {
+            assert_eq!(
+                writer.field_mut(TESTER::ID::MASK).set_u32(7u8).unwrap(),
+                0xFFFF_0000
+            );
+            assert!(matches!(
+                writer.field_mut(TESTER::ID::MASK).set_u32(-1),
+                Err(ShoobyError::InvalidTypeConversion)
+            ));
+            assert_eq!(
+                writer
+                    .field_mut(TESTER::ID::OFFSET)
+                    .set_i64(-9_000_000_000i64)
+                    .unwrap(),
+                -5
+            );
+            assert!(matches!(
+                writer
+                    .field_mut(TESTER::ID::OFFSET)
+                    .set_i64(20_000_000_000i64),
+                Err(ShoobyError::OutOfBounds)
+            ));
+            writer
+                .field_mut(TESTER::ID::UPTIME)
+                .set_u64(u64::MAX)
+                .unwrap();
+        });
+
+        let reader = db.reader();
+        assert_eq!(reader[TESTER::ID::MASK].get_u32::<u8>().unwrap(), 7);
+        assert_eq!(
+            reader[TESTER::ID::OFFSET].get_i64::<i64>().unwrap(),
+            -9_000_000_000
+        );
+        assert_eq!(
+            reader[TESTER::ID::UPTIME].get_u64::<u64>().unwrap(),
+            u64::MAX
+        );
+    }
+
+    #[test]
+    fn numbers_are_persisted_little_endian() {
+        let data = MemStorageData::default();
+
+        {
+            create_db_instance!(TESTER);
+            let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)));
+            db.init().unwrap();
+            db.write_with(|writer| {
+                writer
+                    .field_mut(TESTER::ID::MASK)
+                    .set_u32(0x0403_0201u32)
+                    .unwrap();
+                writer
+                    .field_mut(TESTER::ID::UPTIME)
+                    .set_u64(0x0807_0605_0403_0201u64)
+                    .unwrap();
+            });
+        }
+
+        assert_eq!(
+            data.borrow()["TESTER::ID::MASK"][RECORD_HEADER_SIZE..],
+            [1, 2, 3, 4]
+        );
+        assert_eq!(
+            data.borrow()["TESTER::ID::UPTIME"][RECORD_HEADER_SIZE..],
+            [1, 2, 3, 4, 5, 6, 7, 8]
+        );
+
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)));
+        let report = db.init().unwrap();
+        assert_eq!(report[TESTER::ID::MASK], LoadStatus::Loaded);
+        assert_eq!(report[TESTER::ID::UPTIME], LoadStatus::Loaded);
+        assert_eq!(
+            db.reader()[TESTER::ID::MASK].get_u32::<u32>().unwrap(),
+            0x0403_0201
+        );
+        assert_eq!(
+            db.reader()[TESTER::ID::UPTIME].get_u64::<u64>().unwrap(),
+            0x0807_0605_0403_0201
+        );
+    }
+
+    #[test]
+    fn enum_field() {
+        #[derive(Debug, PartialEq)]
+        enum Other {
+            Only,
+        }
+
+        impl ShoobyEnum for Other {
+            const VARIANTS: &'static [(u32, &'static str)] = &[(0, "Only")];
+
+            fn to_discriminant(&self) -> u32 {
+                0
+            }
+
+            fn from_discriminant(discriminant: u32) -> Option<Self> {
+                (discriminant == 0).then_some(Other::Only)
+            }
+        }
+
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_db_with_empty_observer_and_storage();
+        assert_eq!(
+            db.reader()[TESTER::ID::LEVEL].get_enum::<Level>().unwrap(),
+            Level::Info
+        );
+        assert!(matches!(
+            db.reader()[TESTER::ID::LEVEL].get_enum::<Other>(),
+            Err(ShoobyError::InvalidType)
+        ));
+
+        db.write_with(|writer| {
+            let mut level = writer.field_mut(TESTER::ID::LEVEL);
+            assert_eq!(level.set_enum(Level::Debug).unwrap(), Level::Info);
+            assert!(matches!(
+                level.set_enum(Other::Only),
+                Err(ShoobyError::InvalidType)
+            ));
+        });
+
+        assert_eq!(
+            db.reader()[TESTER::ID::LEVEL].get_enum::<Level>().unwrap(),
+            Level::Debug
+        );
+        assert_eq!(
+            std::format!("{}", ShoobyFieldType::Enum(4, Level::VARIANTS)),
+            "Enum(Debug)"
+        );
+    }
+
+    #[test]
+    fn enum_field_is_validated_on_load() {
+        let data = MemStorageData::default();
+
+        {
+            create_db_instance!(TESTER);
+            let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)));
+            db.init().unwrap();
+            db.write_with(|writer| {
+                writer
+                    .field_mut(TESTER::ID::LEVEL)
+                    .set_enum(Level::Error)
+                    .unwrap();
+            });
+        }
+
+        {
+            create_db_instance!(TESTER);
+            let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)));
+            let report = db.init().unwrap();
+            assert_eq!(report[TESTER::ID::LEVEL], LoadStatus::Loaded);
+            assert_eq!(
+                db.reader()[TESTER::ID::LEVEL].get_enum::<Level>().unwrap(),
+                Level::Error
+            );
+        }
+
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)));
+
+        // 2 is not a discriminant of Level
+        let level = record(&db.reader()[TESTER::ID::LEVEL], &[2, 0, 0, 0]);
+        data.borrow_mut()
+            .insert("TESTER::ID::LEVEL".to_string(), level);
+
+        let report = db.init().unwrap();
+        assert_eq!(report[TESTER::ID::LEVEL], LoadStatus::Corrupted);
+        assert_eq!(
+            db.reader()[TESTER::ID::LEVEL].get_enum::<Level>().unwrap(),
+            Level::Info
+        );
+    }
+
+    #[test]
+    fn changed_layout_is_reset_to_default() {
+        let data = MemStorageData::default();
+
+        {
+            shooby_db!(TESTER =>
+                {NAME, String, "first", 8, PERSISTENT},
+                {RETRIES, Int, 3, None, PERSISTENT},
+            );
+            let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)));
+            db.init().unwrap();
+            db.write_with(|writer| {
+                writer
+                    .field_mut(TESTER::ID::NAME)
+                    .set_string("second")
+                    .unwrap();
+                writer.field_mut(TESTER::ID::RETRIES).set_num(5).unwrap();
+            });
+        }
+
+        {
+            // NAME grew and RETRIES became unsigned
+            shooby_db!(TESTER =>
+                {NAME, String, "first", 16, PERSISTENT},
+                {RETRIES, U32, 3, None, PERSISTENT},
+            );
+            let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)));
+            let report = db.init().unwrap();
+            assert_eq!(report[TESTER::ID::NAME], LoadStatus::LayoutChanged);
+            assert_eq!(report[TESTER::ID::RETRIES], LoadStatus::LayoutChanged);
+            assert_eq!(db.reader()[TESTER::ID::NAME].get_string().unwrap(), "first");
+            assert_eq!(
+                db.reader()[TESTER::ID::RETRIES].get_u32::<u32>().unwrap(),
+                3
+            );
+        }
+
+        // the defaults were saved with the new layout
+        shooby_db!(TESTER =>
+            {NAME, String, "first", 16, PERSISTENT},
+            {RETRIES, U32, 3, None, PERSISTENT},
+        );
+        let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)));
+        let report = db.init().unwrap();
+        assert_eq!(report[TESTER::ID::NAME], LoadStatus::Loaded);
+        assert_eq!(report[TESTER::ID::RETRIES], LoadStatus::Loaded);
+    }
+
+    #[test]
+    fn migration_is_called_when_schema_version_changes() {
+        use core::cell::Cell;
+
+        let data = MemStorageData::default();
+
+        {
+            shooby_db!(TESTER, version = 1 =>
+                {TIMEOUT_SEC, Int, 3, None, PERSISTENT},
+            );
+            let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)));
+            db.init_with_migration(|_, _, _| panic!("nothing to migrate"))
+                .unwrap();
+            db.write_with(|writer| {
+                writer
+                    .field_mut(TESTER::ID::TIMEOUT_SEC)
+                    .set_num(5)
+                    .unwrap();
+            });
+        }
+        assert_eq!(data.borrow()[SCHEMA_VERSION_KEY], 1u32.to_le_bytes());
+
+        let migrated_from = Cell::new(None);
+        {
+            // the field kept its layout but the unit changed from seconds to milliseconds
+            shooby_db!(TESTER, version = 2 =>
+                {TIMEOUT_SEC, Int, 3000, None, PERSISTENT},
+            );
+            let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)));
+            let report = db
+                .init_with_migration(|version, fields, _storage| {
+                    migrated_from.set(Some(version));
+                    let seconds = fields[TESTER::ID::TIMEOUT_SEC].get_int::<i32>().unwrap();
+                    fields[TESTER::ID::TIMEOUT_SEC]
+                        .set_num(seconds * 1000)
+                        .unwrap();
+                })
+                .unwrap();
+
+            assert_eq!(report[TESTER::ID::TIMEOUT_SEC], LoadStatus::Loaded);
+            assert_eq!(migrated_from.get(), Some(1));
+            assert_eq!(
+                db.reader()[TESTER::ID::TIMEOUT_SEC]
+                    .get_int::<i32>()
+                    .unwrap(),
+                5000
+            );
+        }
+        assert_eq!(data.borrow()[SCHEMA_VERSION_KEY], 2u32.to_le_bytes());
+
+        shooby_db!(TESTER, version = 2 =>
+            {TIMEOUT_SEC, Int, 3000, None, PERSISTENT},
+        );
+        let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)));
+        db.init_with_migration(|_, _, _| panic!("already migrated"))
+            .unwrap();
+        assert_eq!(
+            db.reader()[TESTER::ID::TIMEOUT_SEC]
+                .get_int::<i32>()
+                .unwrap(),
+            5000
+        );
+    }
+
+    #[test]
+    fn corrupted_record_falls_back_to_default() {
+        let data = MemStorageData::default();
+
+        {
+            create_db_instance!(TESTER);
+            let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)));
+            db.init().unwrap();
+            db.write_with(|writer| {
+                writer.field_mut(TESTER::ID::NUM).set_num(50).unwrap();
+                writer
+                    .field_mut(TESTER::ID::BOOLEAN)
+                    .set_bool(true)
+                    .unwrap();
+            });
+        }
+
+        // a bit flip in the value of BOOLEAN, it would still be a valid bool
+        data.borrow_mut().get_mut("TESTER::ID::BOOLEAN").unwrap()[RECORD_HEADER_SIZE] ^= 1;
+
+        create_db_instance!(TESTER);
+        let mut storage = MemStorage::new(&data);
+        let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)));
+
+        let mut scratch = [0u8; 64];
+        let mut boolean = ShoobyField::new(
+            ShoobyFieldType::Bool(false),
+            &TESTER::SCHEMA[TESTER::ID::BOOLEAN as usize],
+        );
+        assert!(matches!(
+            boolean.load(&mut storage, &mut scratch),
+            Err(ShoobyError::CorruptedRecord)
+        ));
+
+        let report = db.init().unwrap();
+        assert_eq!(report[TESTER::ID::BOOLEAN], LoadStatus::Corrupted);
+        assert_eq!(db.reader()[TESTER::ID::BOOLEAN].get_bool().unwrap(), false);
+    }
+
+    #[test]
+    fn set_string_replaces_invalid_value() {
+        create_db_instance!(TESTER);
+
+        let buffer = std::boxed::Box::leak(vec![0xff, 0xfe, 0xfd, 0].into_boxed_slice());
+        let mut string = ShoobyField::new(
+            ShoobyFieldType::String(buffer),
+            &TESTER::SCHEMA[TESTER::ID::STRING as usize],
+        );
+
+        assert!(string.get_string().is_err());
+        string.set_string("ok").unwrap();
+        assert_eq!(string.get_string().unwrap(), "ok");
+    }
+
+    #[test]
+    fn flash_storage_persists_fields() {
+        let mut flash = RamFlash::<512, 4>::new();
+
+        {
+            create_db_instance!(TESTER);
+            let storage = FlashStorage::new(&mut flash).unwrap();
+            let mut db = TESTER::take_with_storage_only(Some(storage));
+            db.init().unwrap();
+
+            // enough writes to go around all the sectors a few times
+            for uptime in 1..=400u64 {
+                db.write_with(|writer| {
+                    writer
+                        .field_mut(TESTER::ID::UPTIME)
+                        .set_u64(uptime)
+                        .unwrap();
+                    if uptime == 1 {
+                        writer
+                            .field_mut(TESTER::ID::BOOLEAN)
+                            .set_bool(true)
+                            .unwrap();
+                    }
+                });
+            }
+        }
+
+        for sector in 0..4 {
+            assert!(flash.erase_count(sector) > 2);
+        }
+
+        // reboot
+        create_db_instance!(TESTER);
+        let storage = FlashStorage::new(&mut flash).unwrap();
+        let mut db = TESTER::take_with_storage_only(Some(storage));
+        let report = db.init().unwrap();
+
+        assert_eq!(report[TESTER::ID::UPTIME], LoadStatus::Loaded);
+        assert_eq!(report[TESTER::ID::BOOLEAN], LoadStatus::Loaded);
+        assert_eq!(report[TESTER::ID::MASK], LoadStatus::Default);
+
+        let reader = db.reader();
+        assert_eq!(reader[TESTER::ID::UPTIME].get_u64::<u64>().unwrap(), 400);
+        assert_eq!(reader[TESTER::ID::BOOLEAN].get_bool().unwrap(), true);
+    }
+
+    #[test]
+    fn flash_storage_erases_once_per_rotation() {
+        create_db_instance!(TESTER);
+        let mut storage = FlashStorage::<_, TESTER::ID>::new(RamFlash::<128, 4>::new()).unwrap();
+        for value in 0..400u32 {
+            storage
+                .save_raw(TESTER::ID::MASK, &value.to_le_bytes())
+                .unwrap();
+        }
+
+        // every rotation writes the next sequence, the first 3 take sectors that were never used
+        let flash = storage.flash();
+        let mut rotations = 0;
+        let mut erases = 0;
+        for sector in 0..4 {
+            let mut sequence = [0u8; 4];
+            flash.read(sector * 128, &mut sequence).unwrap();
+            if sequence != [0xff; 4] {
+                rotations = rotations.max(u32::from_le_bytes(sequence));
+            }
+            erases += flash.erase_count(sector);
+        }
+        assert!(rotations > 20);
+        assert_eq!(erases, rotations - 3);
+
+        let mut mask = [0u8; 4];
+        assert!(storage.load_raw(TESTER::ID::MASK, &mut mask).unwrap());
+        assert_eq!(u32::from_le_bytes(mask), 399);
+    }
+
+    #[test]
+    fn flash_storage_survives_power_loss() {
+        create_db_instance!(TESTER);
+
+        for budget in (0..1500).step_by(5) {
+            let mut flash = RamFlash::<256, 3>::new();
+            let mut last_saved = 0u32;
+
+            {
+                let mut storage = FlashStorage::<_, TESTER::ID>::new(&mut flash).unwrap();
+                storage.save_raw(TESTER::ID::LEVEL, &[3]).unwrap();
+                storage.flash().cut_power_after(budget);
+
+                for value in 1..200u32 {
+                    if storage
+                        .save_raw(TESTER::ID::MASK, &value.to_le_bytes())
+                        .is_err()
+                    {
+                        break;
+                    }
+                    last_saved = value;
+                }
+            }
+
+            // reboot
+            flash.restore_power();
+            let mut storage = FlashStorage::<_, TESTER::ID>::new(&mut flash).unwrap();
+
+            let mut mask = [0u8; 4];
+            let found = storage.load_raw(TESTER::ID::MASK, &mut mask).unwrap();
+            let mask = u32::from_le_bytes(mask);
+            assert!(
+                (!found && last_saved == 0) || mask == last_saved || mask == last_saved + 1,
+                "budget {}: loaded {} after saving {}",
+                budget,
+                mask,
+                last_saved
+            );
+
+            let mut level = [0u8; 1];
+            assert!(storage.load_raw(TESTER::ID::LEVEL, &mut level).unwrap());
+            assert_eq!(level, [3]);
+
+            // the storage is still usable
+            for value in 1000..1050u32 {
+                storage
+                    .save_raw(TESTER::ID::MASK, &value.to_le_bytes())
+                    .unwrap();
+            }
+            let mut mask = [0u8; 4];
+            assert!(storage.load_raw(TESTER::ID::MASK, &mut mask).unwrap());
+            assert_eq!(u32::from_le_bytes(mask), 1049);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(std::format!("shoobydb-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn file_storage_persists_fields() {
+        let path = temp_file("persists");
+
+        {
+            create_db_instance!(TESTER);
+            let mut db = TESTER::take_with_storage_only(Some(FileStorage::new(&path)));
+            let report = db.init().unwrap();
+            assert_eq!(report[TESTER::ID::BOOLEAN], LoadStatus::Default);
+
+            db.write_with(|writer| {
+                writer
+                    .field_mut(TESTER::ID::BOOLEAN)
+                    .set_bool(true)
+                    .unwrap();
+                writer.field_mut(TESTER::ID::UPTIME).set_u64(1234).unwrap();
+            });
+        }
+
+        let mut temp_path = path.clone().into_os_string();
+        temp_path.push(".tmp");
+        assert!(!std::path::Path::new(&temp_path).exists());
+
+        // reboot
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_with_storage_only(Some(FileStorage::new(&path)));
+        let report = db.init().unwrap();
+
+        assert_eq!(report[TESTER::ID::BOOLEAN], LoadStatus::Loaded);
+        assert_eq!(report[TESTER::ID::UPTIME], LoadStatus::Loaded);
+        assert_eq!(db.reader()[TESTER::ID::BOOLEAN].get_bool().unwrap(), true);
+        assert_eq!(
+            db.reader()[TESTER::ID::UPTIME].get_u64::<u64>().unwrap(),
+            1234
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn file_storage_tolerates_truncated_file() {
+        let path = temp_file("truncated");
+
+        {
+            create_db_instance!(TESTER);
+            let mut db = TESTER::take_with_storage_only(Some(FileStorage::new(&path)));
+            db.init().unwrap();
+            db.write_with(|writer| {
+                writer
+                    .field_mut(TESTER::ID::BOOLEAN)
+                    .set_bool(true)
+                    .unwrap();
+                writer.field_mut(TESTER::ID::UPTIME).set_u64(1234).unwrap();
+            });
+        }
+
+        // entries are sorted by name, UPTIME is the last one
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_with_storage_only(Some(FileStorage::new(&path)));
+        let report = db.init().unwrap();
+
+        assert_eq!(report[TESTER::ID::BOOLEAN], LoadStatus::Loaded);
+        assert_eq!(report[TESTER::ID::UPTIME], LoadStatus::Default);
+        assert_eq!(db.reader()[TESTER::ID::BOOLEAN].get_bool().unwrap(), true);
+
+        // garbage is ignored as well
+        std::fs::write(&path, b"garbage").unwrap();
+        let mut storage = FileStorage::<TESTER::ID>::new(&path);
+        let mut boolean = [0u8; 1];
+        assert!(!storage.load_raw(TESTER::ID::BOOLEAN, &mut boolean).unwrap());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn file_storage_saves_a_write_in_one_batch() {
+        create_db_instance!(TESTER);
+        let path = temp_file("batch");
+        let storage = FileStorage::<TESTER::ID>::new(&path);
+        let on_disk = |id: TESTER::ID, data: &mut [u8]| {
+            FileStorage::<TESTER::ID>::new(&path)
+                .load_raw(id, data)
+                .unwrap()
+        };
+
+        storage.begin_batch();
+        storage.save_raw(TESTER::ID::BOOLEAN, &[1]).unwrap();
+        storage.save_raw(TESTER::ID::LEVEL, &[3]).unwrap();
+        assert!(!on_disk(TESTER::ID::BOOLEAN, &mut [0]));
+        storage.commit_batch().unwrap();
+        assert!(on_disk(TESTER::ID::BOOLEAN, &mut [0]));
+        assert!(on_disk(TESTER::ID::LEVEL, &mut [0]));
+
+        // an aborted batch is not written
+        storage.begin_batch();
+        storage.save_raw(TESTER::ID::MASK, &[1, 2, 3, 4]).unwrap();
+        storage.abort_batch();
+        storage.commit_batch().unwrap();
+        assert!(!on_disk(TESTER::ID::MASK, &mut [0; 4]));
+        std::fs::remove_file(&path).unwrap();
+
+        // a failed write does not change what is loaded
+        let mut storage = FileStorage::<TESTER::ID>::new(path.join("missing"));
+        assert!(matches!(
+            storage.save_raw(TESTER::ID::BOOLEAN, &[1]),
+            Err(ShoobyError::StorageFailure)
+        ));
+        assert!(!storage.load_raw(TESTER::ID::BOOLEAN, &mut [0]).unwrap());
+    }
+
+    #[test]
+    fn try_write_with_rolls_back_on_error() {
+        use core::cell::Cell;
+        struct CountingObserver<'a> {
+            updates: &'a Cell<usize>,
+        }
+
+        impl<'a> ShoobyObserver for CountingObserver<'a> {
+            type ID = TESTER::ID;
+            fn update(&self, _field: &ShoobyField<Self::ID>) {
+                self.updates.set(self.updates.get() + 1);
+            }
+        }
+
+        let updates = Cell::new(0);
+        let data = MemStorageData::default();
+
+        create_db_instance!(TESTER);
+        let mut db = TESTER::DB::take(
+            Some(CountingObserver { updates: &updates }),
+            Some(MemStorage::new(&data)),
+        );
+        db.init().unwrap();
+
+        let result = db.try_write_with(|writer| {
+            writer.field_mut(TESTER::ID::BOOLEAN).set_bool(true)?;
+            writer.field_mut(TESTER::ID::STRING).set_string("changed")?;
+            writer.field_mut(TESTER::ID::NUM).set_num(1000)?;
+            Ok::<_, ShoobyError>(())
+        });
+
+        assert!(matches!(result, Err(ShoobyError::OutOfBounds)));
+        assert_eq!(updates.get(), 0);
+        assert!(!data.borrow().contains_key("TESTER::ID::BOOLEAN"));
+
+        let reader = db.reader();
+        assert_eq!(reader[TESTER::ID::BOOLEAN].get_bool().unwrap(), false);
+        assert_eq!(reader[TESTER::ID::STRING].get_string().unwrap(), "default");
+        assert_eq!(reader[TESTER::ID::NUM].get_int::<i32>().unwrap(), 15);
+
+        let old = db
+            .try_write_with(|writer| writer.field_mut(TESTER::ID::BOOLEAN).set_bool(true))
+            .unwrap();
+
+        assert_eq!(old, false);
+        assert_eq!(updates.get(), 1);
+        assert!(data.borrow().contains_key("TESTER::ID::BOOLEAN"));
+        assert_eq!(db.reader()[TESTER::ID::BOOLEAN].get_bool().unwrap(), true);
+    }
+
+    #[test]
+    fn try_write_with_returns_storage_error() {
+        struct FailingStorage;
+        impl ShoobyStorage for FailingStorage {
+            type ID = TESTER::ID;
+            fn save_raw(&self, _id: Self::ID, _data: &[u8]) -> Result<(), ShoobyError> {
+                Err(ShoobyError::StorageFailure)
+            }
+            fn load_raw(&mut self, _id: Self::ID, _data: &mut [u8]) -> Result<bool, ShoobyError> {
+                Ok(false)
+            }
+        }
+
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_with_storage_only(Some(FailingStorage));
+
+        let result = db.try_write_with(|writer| {
+            writer.field_mut(TESTER::ID::NUM).set_num(50)?;
+            writer.field_mut(TESTER::ID::UPTIME).set_u64(7)
+        });
+
+        assert!(matches!(result, Err(ShoobyError::StorageFailure)));
+        assert_eq!(db.reader()[TESTER::ID::NUM].get_int::<i32>().unwrap(), 15);
+        assert_eq!(db.reader()[TESTER::ID::UPTIME].get_u64::<u64>().unwrap(), 0);
+    }
+
+    #[cfg(any(feature = "std", feature = "critical-section"))]
+    #[test]
+    fn panicking_write_is_rolled_back() {
+        use core::cell::RefCell;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        struct ChangeObserver<'a> {
+            changes: &'a RefCell<Vec<(TESTER::ID, String, String)>>,
+        }
+
+        impl<'a> ShoobyObserver for ChangeObserver<'a> {
+            type ID = TESTER::ID;
+            fn update(&self, _field: &ShoobyField<Self::ID>) {}
+            fn on_change(&self, change: &ShoobyChange<'_, Self::ID>) {
+                self.changes.borrow_mut().push((
+                    change.id,
+                    change.old.to_string(),
+                    change.new.to_string(),
+                ));
+            }
+        }
+
+        let changes = RefCell::new(Vec::new());
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_with_observer_only(Some(ChangeObserver { changes: &changes }));
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            db.write_with(|writer| {
+                writer.field_mut(TESTER::ID::NUM).set_num(20).unwrap();
+                panic!("the closure failed half way");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(db.reader()[TESTER::ID::NUM].get_int::<i32>().unwrap(), 15);
+        assert!(db.reader().iter().all(|field| !field.has_changed));
+
+        db.write_with(|writer| {
+            writer.field_mut(TESTER::ID::NUM).set_num(30).unwrap();
+        });
+        assert_eq!(
+            *changes.borrow(),
+            vec![(TESTER::ID::NUM, "15".to_string(), "30".to_string())]
+        );
+
+        // the lock of a shared DB is poisoned, but it still holds the values from before the write
+        let shared = SharedDB::new(db);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            shared.write_with(|writer| {
+                writer
+                    .field_mut(TESTER::ID::STRING)
+                    .set_string("half")
+                    .unwrap();
+                panic!("the closure failed half way");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            shared.reader()[TESTER::ID::STRING].get_string().unwrap(),
+            "default"
+        );
+        assert_eq!(
+            shared.reader()[TESTER::ID::NUM].get_int::<i32>().unwrap(),
+            30
+        );
+    }
+
+    #[cfg(any(feature = "std", feature = "critical-section"))]
+    #[test]
+    fn shared_db_stress() {
+        const WRITERS: u64 = 4;
+        const WRITES: u64 = 500;
+
+        create_db_instance!(TESTER);
+        let shared = SharedDB::new(TESTER::take_db_with_empty_observer_and_storage());
+
+        std::thread::scope(|scope| {
+            for writer in 0..WRITERS {
+                let shared = &shared;
+                scope.spawn(move || {
+                    for i in 0..WRITES {
+                        let value = writer * WRITES + i;
+                        // UPTIME and MASK are always written together
+                        shared.write_with(|fields| {
+                            fields.field_mut(TESTER::ID::UPTIME).set_u64(value).unwrap();
+                            fields
+                                .field_mut(TESTER::ID::MASK)
+                                .set_u32(value as u32)
+                                .unwrap();
+                        });
+                    }
+                });
+            }
+
+            for _ in 0..4 {
+                let shared = &shared;
+                scope.spawn(move || {
+                    for _ in 0..WRITES {
+                        let reader = shared.reader();
+                        let uptime = reader[TESTER::ID::UPTIME].get_u64::<u64>().unwrap();
+                        let mask = reader[TESTER::ID::MASK].get_u32::<u32>().unwrap();
+                        assert!(uptime == 0 && mask == 0xFFFF_0000 || uptime == mask as u64);
+                    }
+                });
+            }
+        });
+
+        // many readers at once, and no writer while they are alive
+        let first = shared.reader();
+        let second = shared.try_reader().unwrap();
+        assert_eq!(
+            first[TESTER::ID::UPTIME].get_u64::<u64>().unwrap(),
+            second[TESTER::ID::UPTIME].get_u64::<u64>().unwrap()
+        );
+        assert!(shared
+            .try_write_with_now(|fields| fields.field_mut(TESTER::ID::BOOLEAN).set_bool(true))
+            .is_none());
+        drop(first);
+        drop(second);
+
+        assert!(shared
+            .try_write_with_now(|fields| fields.field_mut(TESTER::ID::BOOLEAN).set_bool(true))
+            .is_some());
+        let db = shared.into_inner();
+        assert_eq!(db.reader()[TESTER::ID::BOOLEAN].get_bool().unwrap(), true);
+    }
+
+    #[test]
+    fn observer_gets_old_and_new_values() {
+        use core::cell::RefCell;
+        struct ChangeObserver<'a> {
+            changes: &'a RefCell<Vec<(TESTER::ID, String, String)>>,
+        }
+
+        impl<'a> ShoobyObserver for ChangeObserver<'a> {
+            type ID = TESTER::ID;
+            fn update(&self, _field: &ShoobyField<Self::ID>) {}
+            fn on_change(&self, change: &ShoobyChange<'_, Self::ID>) {
+                assert_eq!(change.new, change.field.value());
+                self.changes.borrow_mut().push((
+                    change.id,
+                    change.old.to_string(),
+                    change.new.to_string(),
+                ));
+            }
+        }
+
+        let changes = RefCell::new(Vec::new());
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_with_observer_only(Some(ChangeObserver { changes: &changes }));
+
+        db.write_with(|writer| {
+            writer.field_mut(TESTER::ID::NUM).set_num(90).unwrap();
+            writer
+                .field_mut(TESTER::ID::STRING)
+                .set_string("new")
+                .unwrap();
+            writer
+                .field_mut(TESTER::ID::LEVEL)
+                .set_enum(Level::Debug)
+                .unwrap();
+        });
+
+        assert_eq!(
+            *changes.borrow(),
+            vec![
+                (TESTER::ID::NUM, "15".to_string(), "90".to_string()),
+                (TESTER::ID::STRING, "default".to_string(), "new".to_string()),
+                (TESTER::ID::LEVEL, "Info".to_string(), "Debug".to_string()),
+            ]
+        );
+
+        // the previous value is staged on the first change of the field only
+        changes.borrow_mut().clear();
+        db.write_with(|writer| {
+            writer.field_mut(TESTER::ID::NUM).set_num(20).unwrap();
+            writer.field_mut(TESTER::ID::NUM).set_num(30).unwrap();
+        });
+        assert_eq!(
+            *changes.borrow(),
+            vec![(TESTER::ID::NUM, "90".to_string(), "30".to_string())]
+        );
+    }
+
+    #[test]
+    fn observers_get_only_interesting_fields() {
+        use core::cell::RefCell;
+        struct FieldsObserver<'a> {
+            fields: TESTER::IdSet,
+            seen: &'a RefCell<Vec<TESTER::ID>>,
+        }
+
+        impl<'a> ShoobyObserver for FieldsObserver<'a> {
+            type ID = TESTER::ID;
+            fn is_interested(&self, id: &Self::ID) -> bool {
+                self.fields.contains(*id)
+            }
+            fn update(&self, field: &ShoobyField<Self::ID>) {
+                assert!(self.fields.contains(field.id()));
+                self.seen.borrow_mut().push(field.id());
+            }
+        }
+
+        const NUMBERS: TESTER::IdSet = TESTER::id_set(&[TESTER::ID::NUM, TESTER::ID::UPTIME]);
+        assert!(NUMBERS.contains(TESTER::ID::UPTIME));
+        assert!(!NUMBERS.contains(TESTER::ID::STRING));
+        assert_eq!(
+            NUMBERS.iter().collect::<Vec<_>>(),
+            vec![TESTER::ID::NUM, TESTER::ID::UPTIME]
+        );
+        assert_eq!(TESTER::IdSet::all().iter().count(), 9);
+
+        let mut strings = TESTER::IdSet::empty();
+        strings.insert(TESTER::ID::STRING);
+        strings.insert(TESTER::ID::NUM);
+        strings.remove(TESTER::ID::NUM);
+
+        let numbers_seen = RefCell::new(Vec::new());
+        let strings_seen = RefCell::new(Vec::new());
+        let mut multi_observer: MultiObserver<TESTER::ID, FieldsObserver, 2> = MultiObserver::new();
+        multi_observer
+            .add(FieldsObserver {
+                fields: NUMBERS,
+                seen: &numbers_seen,
+            })
+            .unwrap();
+        multi_observer
+            .add(FieldsObserver {
+                fields: strings,
+                seen: &strings_seen,
+            })
+            .unwrap();
+
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_with_observer_only(Some(multi_observer));
+
+        db.write_with(|writer| {
+            writer.field_mut(TESTER::ID::NUM).set_num(90).unwrap();
+            writer
+                .field_mut(TESTER::ID::BOOLEAN)
+                .set_bool(true)
+                .unwrap();
+            writer
+                .field_mut(TESTER::ID::STRING)
+                .set_string("new")
+                .unwrap();
+        });
+
+        assert_eq!(*numbers_seen.borrow(), vec![TESTER::ID::NUM]);
+        assert_eq!(*strings_seen.borrow(), vec![TESTER::ID::STRING]);
+    }
+
+    #[test]
+    fn interceptor_rejects_values() {
+        struct Policy {
+            max: i32,
+        }
+        impl ShoobyInterceptor for Policy {
+            type ID = TESTER::ID;
+            fn before_write(
+                &self,
+                field: &ShoobyField<Self::ID>,
+                new: &ShoobyValue<'_>,
+            ) -> Result<(), ShoobyError> {
+                match (field.id(), new) {
+                    (TESTER::ID::STRING, ShoobyValue::String(new)) if new.len() < 8 => {
+                        Err(ShoobyError::Rejected("too short"))
+                    }
+                    (TESTER::ID::NUM, ShoobyValue::Int(new)) if *new > self.max => {
+                        Err(ShoobyError::Rejected("too big"))
+                    }
+                    _ => Ok(()),
+                }
+            }
+        }
+
+        let data = MemStorageData::default();
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)))
+            .with_interceptor(Policy { max: 50 });
+
+        db.write_with(|writer| {
+            assert!(matches!(
+                writer.field_mut(TESTER::ID::STRING).set_string("abc"),
+                Err(ShoobyError::Rejected("too short"))
+            ));
+            writer
+                .field_mut(TESTER::ID::STRING)
+                .set_string("long enough")
+                .unwrap();
+            assert!(matches!(
+                writer.field_mut(TESTER::ID::NUM).set_num(60),
+                Err(ShoobyError::Rejected("too big"))
+            ));
+            writer.field_mut(TESTER::ID::NUM).set_num(40).unwrap();
+        });
+
+        let result = db.try_write_with(|writer| {
+            writer.field_mut(TESTER::ID::BOOLEAN).set_bool(true)?;
+            writer.field_mut(TESTER::ID::NUM).set_num(99)
+        });
+        assert!(matches!(result, Err(ShoobyError::Rejected("too big"))));
+        assert!(!data.borrow().contains_key("TESTER::ID::BOOLEAN"));
+
+        let reader = db.reader();
+        assert_eq!(
+            reader[TESTER::ID::STRING].get_string().unwrap(),
+            "long enough"
+        );
+        assert_eq!(reader[TESTER::ID::NUM].get_int::<i32>().unwrap(), 40);
+        assert_eq!(reader[TESTER::ID::BOOLEAN].get_bool().unwrap(), false);
+
+        // the policy is owned by the DB and can change at runtime
+        db.interceptor(|policy| policy.max = 100);
+        db.try_write_with(|writer| writer.field_mut(TESTER::ID::NUM).set_num(99))
+            .unwrap();
+        assert_eq!(db.reader()[TESTER::ID::NUM].get_int::<i32>().unwrap(), 99);
+
+        // defaults are not intercepted, even if they would be rejected
+        db.factory_reset().unwrap();
+        assert_eq!(
+            db.reader()[TESTER::ID::STRING].get_string().unwrap(),
+            "default"
+        );
+    }
+
+    #[test]
+    fn dyn_multi_observer() {
+        use core::cell::RefCell;
+        struct Named<'a> {
+            name: &'static str,
+            calls: &'a RefCell<Vec<&'static str>>,
+        }
+
+        impl<'a> ShoobyObserver for Named<'a> {
+            type ID = TESTER::ID;
+            fn update(&self, _field: &ShoobyField<Self::ID>) {
+                self.calls.borrow_mut().push(self.name);
+            }
+        }
+
+        struct OnlyStrings<'a> {
+            calls: &'a RefCell<Vec<&'static str>>,
+        }
+
+        impl<'a> ShoobyObserver for OnlyStrings<'a> {
+            type ID = TESTER::ID;
+            fn is_interested(&self, id: &Self::ID) -> bool {
+                *id == TESTER::ID::STRING
+            }
+            fn update(&self, _field: &ShoobyField<Self::ID>) {}
+            fn on_change(&self, _change: &ShoobyChange<'_, Self::ID>) {
+                self.calls.borrow_mut().push("strings");
+            }
+        }
+
+        let calls = RefCell::new(Vec::new());
+        let logger = Named {
+            name: "logger",
+            calls: &calls,
+        };
+        let ui = Named {
+            name: "ui",
+            calls: &calls,
+        };
+        let strings = OnlyStrings { calls: &calls };
+
+        let mut observers = DynMultiObserver::<TESTER::ID, 3>::new();
+        let logger_handle = observers.add(&logger).unwrap();
+        observers.add(&strings).unwrap();
+        observers.add(&ui).unwrap();
+        assert!(matches!(
+            observers.add(&logger),
+            Err(ShoobyError::OutOfBounds)
+        ));
+
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_with_observer_only(Some(observers));
+
+        db.write_with(|writer| {
+            writer.field_mut(TESTER::ID::NUM).set_num(90).unwrap();
+        });
+        assert_eq!(*calls.borrow(), vec!["logger", "ui"]);
+
+        calls.borrow_mut().clear();
+        db.observer(|observers| {
+            assert!(observers.unwrap().remove(logger_handle).is_some());
+        });
+        db.write_with(|writer| {
+            writer
+                .field_mut(TESTER::ID::STRING)
+                .set_string("new")
+                .unwrap();
+        });
+        assert_eq!(*calls.borrow(), vec!["strings", "ui"]);
+
+        db.observer(|observers| {
+            assert!(observers.unwrap().remove(logger_handle).is_none());
+        });
+    }
+
+    #[test]
+    fn fn_multi_observer() {
+        use core::cell::RefCell;
+        fn ignore(_change: &ShoobyChange<'_, TESTER::ID>) {}
+
+        let changes = RefCell::new(Vec::new());
+        let first = |change: &ShoobyChange<'_, TESTER::ID>| {
+            changes
+                .borrow_mut()
+                .push(std::format!("first {}", change.new))
+        };
+        let second = |change: &ShoobyChange<'_, TESTER::ID>| {
+            changes
+                .borrow_mut()
+                .push(std::format!("second {}", change.new))
+        };
+
+        let mut observers = FnMultiObserver::<TESTER::ID, 3>::new();
+        let first_handle = observers.add(&first).unwrap();
+        observers.add(&ignore).unwrap();
+        observers.add(&second).unwrap();
+
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_with_observer_only(Some(observers));
+
+        db.write_with(|writer| {
+            writer.field_mut(TESTER::ID::NUM).set_num(90).unwrap();
+        });
+        assert_eq!(*changes.borrow(), vec!["first 90", "second 90"]);
+
+        changes.borrow_mut().clear();
+        db.observer(|observers| {
+            let observers = observers.unwrap();
+            observers.remove(first_handle).unwrap();
+            observers.add(&first).unwrap();
+        });
+        db.write_with(|writer| {
+            writer.field_mut(TESTER::ID::NUM).set_num(20).unwrap();
+        });
+        assert_eq!(*changes.borrow(), vec!["second 20", "first 20"]);
+    }
+
+    #[test]
+    fn multi_observer_handles() {
+        use core::cell::RefCell;
+        struct Named<'a> {
+            name: &'static str,
+            calls: &'a RefCell<Vec<&'static str>>,
+        }
+
+        impl<'a> ShoobyObserver for Named<'a> {
+            type ID = TESTER::ID;
+            fn update(&self, _field: &ShoobyField<Self::ID>) {
+                self.calls.borrow_mut().push(self.name);
+            }
+        }
+
+        let calls = RefCell::new(Vec::new());
+        let named = |name| Named {
+            name,
+            calls: &calls,
+        };
+
+        let mut multi_observer: MultiObserver<TESTER::ID, Named, 3> = MultiObserver::new();
+        let handles = [
+            multi_observer.add(named("a")).unwrap(),
+            multi_observer.add(named("b")).unwrap(),
+            multi_observer.add(named("c")).unwrap(),
+        ];
+        assert!(multi_observer.add(named("d")).is_err());
+
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_with_observer_only(Some(multi_observer));
+        let mut num = 20;
+        let mut notify = |db: &mut TESTER::DB<MultiObserver<_, _, 3>>| {
+            calls.borrow_mut().clear();
+            num += 1;
+            db.write_with(|writer| {
+                writer.field_mut(TESTER::ID::NUM).set_num(num).unwrap();
+            });
+            calls.borrow().clone()
+        };
+
+        assert_eq!(notify(&mut db), vec!["a", "b", "c"]);
+
+        // the freed place is reused, the new observer is notified last
+        let mut d_handle = None;
+        db.observer(|observers| {
+            let observers = observers.unwrap();
+            assert_eq!(observers.remove(handles[1]).unwrap().name, "b");
+            assert!(observers.remove(handles[1]).is_none());
+            d_handle = Some(observers.add(named("d")).unwrap());
+        });
+        assert!(!handles.contains(&d_handle.unwrap()));
+        assert_eq!(notify(&mut db), vec!["a", "c", "d"]);
+
+        // remove the observers one by one while notifying in between
+        let mut expected = vec!["a", "c", "d"];
+        for handle in [handles[0], d_handle.unwrap(), handles[2]] {
+            db.observer(|observers| {
+                let removed = observers.unwrap().remove(handle).unwrap();
+                expected.retain(|name| *name != removed.name);
+            });
+            assert_eq!(notify(&mut db), expected);
+        }
+
+        db.observer(|observers| {
+            let observers = observers.unwrap();
+            assert!(observers.is_empty());
+            observers.add(named("e")).unwrap();
+            assert_eq!(observers.len(), 1);
+        });
+        assert_eq!(notify(&mut db), vec!["e"]);
+    }
+
+    #[test]
+    fn multi_observer_unsubscribe_while_notified() {
+        use core::cell::{OnceCell, RefCell};
+        type Observers<'a> = MultiObserver<TESTER::ID, Remover<'a>, 4>;
+        struct Remover<'a> {
+            name: &'static str,
+            // the indexes of the handles it unsubscribes when notified
+            removes: &'static [usize],
+            calls: &'a RefCell<Vec<&'static str>>,
+            handles: &'a OnceCell<Vec<ObserverHandle>>,
+            observers: &'a OnceCell<&'a Observers<'a>>,
+        }
+
+        impl<'a> ShoobyObserver for Remover<'a> {
+            type ID = TESTER::ID;
+            fn update(&self, _field: &ShoobyField<Self::ID>) {
+                self.calls.borrow_mut().push(self.name);
+                for index in self.removes {
+                    let handle = self.handles.get().unwrap()[*index];
+                    self.observers.get().unwrap().unsubscribe(handle);
+                }
+            }
+        }
+
+        let calls = RefCell::new(Vec::new());
+        let handles = OnceCell::new();
+        let observers_ref = OnceCell::new();
+        let remover = |name, removes| Remover {
+            name,
+            removes,
+            calls: &calls,
+            handles: &handles,
+            observers: &observers_ref,
+        };
+
+        // b unsubscribes itself and c, d unsubscribes a which was already notified
+        // leaked because the observers refer to it, so it can't be dropped before them
+        let observers: &mut Observers =
+            std::boxed::Box::leak(std::boxed::Box::new(MultiObserver::new()));
+        let added = vec![
+            observers.add(remover("a", &[])).unwrap(),
+            observers.add(remover("b", &[1, 2])).unwrap(),
+            observers.add(remover("c", &[])).unwrap(),
+            observers.add(remover("d", &[0])).unwrap(),
+        ];
+        handles.set(added).unwrap();
+        let observers: &Observers = observers;
+        observers_ref.set(observers).ok().unwrap();
+
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_with_observer_only(Some(observers));
+        let mut num = 20;
+        let mut notify = || {
+            calls.borrow_mut().clear();
+            num += 1;
+            db.write_with(|writer| {
+                writer.field_mut(TESTER::ID::NUM).set_num(num).unwrap();
+            });
+            calls.borrow().clone()
+        };
+
+        assert_eq!(notify(), vec!["a", "b", "d"]);
+        assert_eq!(notify(), vec!["d"]);
+        assert_eq!(notify(), vec!["d"]);
+        assert_eq!(observers.len(), 1);
+        let handles = handles.get().unwrap();
+        assert!(!observers.unsubscribe(handles[1]));
+        assert!(observers.unsubscribe(handles[3]));
+        assert!(notify().is_empty());
+        assert!(observers.is_empty());
+    }
+
+    #[test]
+    fn typed_accessors() {
+        use TESTER::{Reader, Writer};
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_db_with_empty_observer_and_storage();
+
+        let reader = db.reader();
+        assert_eq!(reader.num(), 15);
+        assert_eq!(reader.string(), "default");
+        assert!(!reader.boolean());
+        assert_eq!(reader.float(), 0.5);
+        assert_eq!(reader.mask(), 0xFFFF_0000);
+        assert_eq!(reader.offset(), -5);
+        assert_eq!(reader.uptime(), 0);
+        assert_eq!(reader.level(), Level::Info);
+        // no type given for the blob, so its getter is generic
+        assert_eq!({ reader.blob::<A>().unwrap().b }, 9);
+
+        db.write_with(|writer| {
+            assert_eq!(writer.set_num(20u8).unwrap(), 15);
+            assert_eq!(writer.set_level(Level::Debug).unwrap(), Level::Info);
+            writer.set_string("typed").unwrap();
+            writer.set_offset(-7i8).unwrap();
+            writer.set_blob(&A { a: 1, b: 2 }).unwrap();
+            assert!(matches!(writer.set_num(500), Err(ShoobyError::OutOfBounds)));
+            // the generic blob setter takes any type, one of another size is an error
+            assert!(matches!(
+                writer.set_blob(&7u8),
+                Err(ShoobyError::InvalidSize)
+            ));
+        });
+
+        let reader = db.reader();
+        assert_eq!(reader.num(), 20);
+        assert_eq!(reader.level(), Level::Debug);
+        assert_eq!(reader.string(), "typed");
+        assert_eq!(reader.offset(), -7);
+        // the index based API still sees the same fields
+        assert_eq!(reader[TESTER::ID::NUM].get_int::<i32>().unwrap(), 20);
+    }
+
+    #[test]
+    fn typed_blob_accessors() {
+        use TYPED::{Reader, Writer};
+        shooby_db!(TYPED =>
+            {SETTINGS, Blob(A), A {a: 5, b: 9}, core::mem::size_of::<A>(), NON_PERSISTENT},
+        );
+        let mut db = TYPED::take_db_with_empty_observer_and_storage();
+
+        assert_eq!({ db.reader().settings().a }, 5);
+        db.write_with(|writer| writer.set_settings(&A { a: 3, b: 4 }).unwrap());
+        assert_eq!({ db.reader().settings().b }, 4);
+    }
+
+    #[test]
+    fn defaults_are_checked_at_compile_time() {
+        // defaults on the edges of their range or size are accepted
+        shooby_db!(EDGES =>
+            {MIN, Int, -3, Some((-3, 3)), NON_PERSISTENT},
+            {MAX, U64, 9, Some((1, 9)), NON_PERSISTENT},
+            {RATIO, Float, 1.0, Some((0.0, 1.0)), NON_PERSISTENT},
+            {NAME, String, "exactly", 7, NON_PERSISTENT},
+            {SETTINGS, Blob, A {a: 1, b: 2}, 8, NON_PERSISTENT},
+        );
+        let db = EDGES::take_db_with_empty_observer_and_storage();
+        assert_eq!(
+            db.reader()[EDGES::ID::NAME].get_string().unwrap(),
+            "exactly"
+        );
+
+        // the checks behind the const assertions
+        assert!(!ShoobyRange::int_allows(Some((10, 100)), 500));
+        assert!(!ShoobyRange::i64_allows(Some((-10, 10)), -11));
+        assert!(!ShoobyRange::float_allows(Some((-1.0, 1.0)), 1.5));
+        assert!(!ShoobyRange::float_allows(None, f32::NAN));
+        assert!(!ShoobyRange::float_allows(None, f32::INFINITY));
+        assert!(!ShoobyRange::float_allows(
+            Some((f32::MIN, f32::MAX)),
+            f32::NAN
+        ));
+        assert!(ShoobyRange::float_allows(None, f32::MAX));
+        assert!(ShoobyRange::u32_allows(None, u32::MAX));
+        assert_eq!(size_of_value(&A { a: 1, b: 2 }), 8);
+    }
+
+    #[test]
+    fn schema_describes_the_fields() {
+        create_db_instance!(TESTER);
+        let db = TESTER::take_db_with_empty_observer_and_storage();
+
+        assert_eq!(TESTER::SCHEMA.len(), TESTER::ID::FIELD_NUM as usize);
+        for (meta, field) in TESTER::SCHEMA.iter().zip(db.reader().iter()) {
+            assert_eq!(meta.id(), field.id());
+            assert_eq!(meta.name(), field.name());
+            assert_eq!(meta.kind(), field.kind());
+            assert_eq!(meta.capacity(), field.capacity());
+            // right after take every field holds its default
+            assert_eq!(meta.default(), field.value());
+        }
+
+        let num = &TESTER::SCHEMA[TESTER::ID::NUM as usize];
+        assert_eq!(num.kind(), ShoobyKind::Int);
+        assert_eq!(num.default(), ShoobyValue::Int(15));
+        assert_eq!(num.range(), Some(ShoobyRange::Int(10, 100)));
+        assert_eq!(num.capacity(), 4);
+        assert!(!num.is_persistent());
+
+        let string = &db.reader()[TESTER::ID::STRING];
+        assert_eq!(string.kind(), ShoobyKind::String);
+        assert_eq!(string.default(), ShoobyValue::String("default"));
+        assert_eq!(string.capacity(), 24);
+        assert_eq!(string.range(), None);
+
+        let level = &TESTER::SCHEMA[TESTER::ID::LEVEL as usize];
+        assert_eq!(level.default(), ShoobyValue::Enum(3, "Info"));
+        assert_eq!(level.variants(), Level::VARIANTS);
+        assert!(level.is_persistent());
+        assert!(TESTER::SCHEMA[TESTER::ID::MASK as usize]
+            .variants()
+            .is_empty());
+
+        let blob = &TESTER::SCHEMA[TESTER::ID::BLOB as usize];
+        assert_eq!(blob.kind(), ShoobyKind::Blob);
+        assert_eq!(blob.capacity(), core::mem::size_of::<A>());
+        assert_eq!(blob.default(), ShoobyValue::Blob(&[5, 0, 0, 0, 9, 0, 0, 0]));
+    }
+
+    #[test]
+    fn fields_by_name() {
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_db_with_empty_observer_and_storage();
+
+        assert_eq!(TESTER::ID::from_name("NUM"), Some(TESTER::ID::NUM));
+        assert_eq!(
+            TESTER::ID::from_name("TESTER::ID::LEVEL"),
+            Some(TESTER::ID::LEVEL)
+        );
+        assert_eq!(TESTER::ID::from_name("num"), None);
+        assert_eq!(TESTER::ID::from_name("OTHER::ID::NUM"), None);
+        assert_eq!(TESTER::ID::from_name("FIELD_NUM"), None);
+        assert_eq!("STRING".parse::<TESTER::ID>().unwrap(), TESTER::ID::STRING);
+        assert!(matches!(
+            "MISSING".parse::<TESTER::ID>(),
+            Err(ShoobyError::UnknownField)
+        ));
+
+        // every generated name parses back to its ID
+        for meta in TESTER::SCHEMA.iter() {
+            assert_eq!(TESTER::ID::from_name(meta.name()), Some(meta.id()));
+        }
+
+        assert_eq!(
+            db.field_by_name("STRING").unwrap().get_string().unwrap(),
+            "default"
+        );
+        assert!(db.field_by_name("MISSING").is_none());
+
+        db.write_with(|writer| {
+            writer
+                .field_by_name_mut("TESTER::ID::NUM")
+                .unwrap()
+                .set_num(42)
+                .unwrap();
+            assert!(writer.field_by_name_mut("MISSING").is_none());
+        });
+        assert_eq!(db.reader()[TESTER::ID::NUM].get_int::<i32>().unwrap(), 42);
+    }
+
+    #[test]
+    fn fields_from_and_to_text() {
+        use std::string::String;
+
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_db_with_empty_observer_and_storage();
+
+        let text = |db: &TESTER::DB, id: TESTER::ID| {
+            let mut out = String::new();
+            db.reader()[id].write_value_to(&mut out).unwrap();
+            out
+        };
+
+        db.write_with(|writer| {
+            writer
+                .field_mut(TESTER::ID::NUM)
+                .set_from_str("42")
+                .unwrap();
+            writer
+                .field_mut(TESTER::ID::BOOLEAN)
+                .set_from_str("true")
+                .unwrap();
+            writer
+                .field_mut(TESTER::ID::STRING)
+                .set_from_str("hello")
+                .unwrap();
+            writer
+                .field_mut(TESTER::ID::FLOAT)
+                .set_from_str("-0.25")
+                .unwrap();
+            writer
+                .field_mut(TESTER::ID::MASK)
+                .set_from_str("4294967295")
+                .unwrap();
+            writer
+                .field_mut(TESTER::ID::OFFSET)
+                .set_from_str("-9000000000")
+                .unwrap();
+            writer
+                .field_mut(TESTER::ID::UPTIME)
+                .set_from_str("18446744073709551615")
+                .unwrap();
+            writer
+                .field_mut(TESTER::ID::LEVEL)
+                .set_from_str("Debug")
+                .unwrap();
+            writer
+                .field_mut(TESTER::ID::BLOB)
+                .set_from_str("0100000002000000")
+                .unwrap();
+        });
+
+        assert_eq!(text(&db, TESTER::ID::NUM), "42");
+        assert_eq!(text(&db, TESTER::ID::BOOLEAN), "true");
+        assert_eq!(text(&db, TESTER::ID::STRING), "hello");
+        assert_eq!(text(&db, TESTER::ID::FLOAT), "-0.25");
+        assert_eq!(text(&db, TESTER::ID::MASK), "4294967295");
+        assert_eq!(text(&db, TESTER::ID::OFFSET), "-9000000000");
+        assert_eq!(text(&db, TESTER::ID::UPTIME), "18446744073709551615");
+        assert_eq!(text(&db, TESTER::ID::LEVEL), "Debug");
+        assert_eq!(text(&db, TESTER::ID::BLOB), "0100000002000000");
+        assert_eq!(
+            { db.reader()[TESTER::ID::BLOB].get_blob::<A>().unwrap().b },
+            2
+        );
+
+        // what write_value_to writes, set_from_str reads back
+        db.write_with(|writer| {
+            for mut field in writer.iter_mut() {
+                let mut out = String::new();
+                field.write_value_to(&mut out).unwrap();
+                field.set_from_str(&out).unwrap();
+                assert!(!field.has_changed);
+            }
+        });
+
+        db.write_with(|writer| {
+            let mut num = writer.field_mut(TESTER::ID::NUM);
+            assert!(matches!(
+                num.set_from_str("500"),
+                Err(ShoobyError::OutOfBounds)
+            ));
+            assert!(matches!(
+                num.set_from_str("4x"),
+                Err(ShoobyError::InvalidTypeConversion)
+            ));
+            assert!(matches!(
+                writer.field_mut(TESTER::ID::BOOLEAN).set_from_str("yes"),
+                Err(ShoobyError::InvalidTypeConversion)
+            ));
+            assert!(matches!(
+                writer
+                    .field_mut(TESTER::ID::STRING)
+                    .set_from_str("this is longer than the 24 bytes"),
+                Err(ShoobyError::OutOfBounds)
+            ));
+            // a discriminant works as well as a name, but only of a variant
+            writer
+                .field_mut(TESTER::ID::LEVEL)
+                .set_from_str("1")
+                .unwrap();
+            assert!(matches!(
+                writer.field_mut(TESTER::ID::LEVEL).set_from_str("2"),
+                Err(ShoobyError::OutOfBounds)
+            ));
+            assert!(matches!(
+                writer.field_mut(TESTER::ID::BLOB).set_from_str("0102"),
+                Err(ShoobyError::InvalidSize)
+            ));
+            assert!(matches!(
+                writer
+                    .field_mut(TESTER::ID::BLOB)
+                    .set_from_str("01000000020000zz"),
+                Err(ShoobyError::InvalidTypeConversion)
+            ));
+        });
+
+        assert_eq!(text(&db, TESTER::ID::NUM), "42");
+        assert_eq!(text(&db, TESTER::ID::LEVEL), "Error");
+        assert_eq!(text(&db, TESTER::ID::BLOB), "0100000002000000");
+    }
+
+    /// An in memory serial port for the shell
+    struct MemSerial {
+        input: Vec<u8>,
+        read: usize,
+        output: Vec<u8>,
+    }
+
+    impl MemSerial {
+        fn new(input: &str) -> Self {
+            Self {
+                input: input.as_bytes().to_vec(),
+                read: 0,
+                output: Vec::new(),
+            }
+        }
+
+        fn output(&self) -> &str {
+            core::str::from_utf8(&self.output).unwrap()
+        }
+    }
+
+    impl embedded_io::ErrorType for MemSerial {
+        type Error = core::convert::Infallible;
+    }
+
+    impl embedded_io::Read for MemSerial {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            // a few bytes at a time, like a UART
+            let len = buf.len().min(5).min(self.input.len() - self.read);
+            buf[..len].copy_from_slice(&self.input[self.read..self.read + len]);
+            self.read += len;
+            Ok(len)
+        }
+    }
+
+    impl embedded_io::Write for MemSerial {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.output.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn shell_commands() {
+        create_db_instance!(TESTER);
+        let data = MemStorageData::default();
+        let mut db = TESTER::take_with_storage_only(Some(MemStorage::new(&data)));
+        db.init().unwrap();
+
+        let mut serial = MemSerial::new(
+            "get NUM\n\
+             set NUM 42\r\n\
+             get TESTER::ID::NUM\n\
+             set NUM 500\n\
+             set STRING hello world\n\
+             get STRING\n\
+             set LEVEL Debug\n\
+             set BOOLEAN true\n\
+             \n\
+             get MISSING\n\
+             set MISSING 1\n\
+             format\n",
+        );
+        let mut shell = ShoobyShell::<64>::new();
+        shell.run(&mut db, &mut serial).unwrap();
+
+        assert_eq!(
+            serial.output(),
+            "15\r\n\
+             OK\r\n\
+             42\r\n\
+             error: Out of bounds\r\n\
+             OK\r\n\
+             hello world\r\n\
+             OK\r\n\
+             OK\r\n\
+             error: Unknown field\r\n\
+             error: Unknown field\r\n\
+             error: unknown command, try help\r\n"
+        );
+        // the shell writes through the DB, so persistent fields are saved
+        assert!(data.borrow().contains_key("TESTER::ID::BOOLEAN"));
+
+        let mut serial = MemSerial::new("list\n");
+        shell.run(&mut db, &mut serial).unwrap();
+        assert_eq!(
+            serial.output(),
+            "NUM Int = 42 (10..=100) volatile\r\n\
+             STRING String = hello world (max 24) volatile\r\n\
+             BOOLEAN Bool = true persistent\r\n\
+             BLOB Blob = 0500000009000000 persistent\r\n\
+             FLOAT Float = 0.5 (-1..=1) persistent\r\n\
+             MASK U32 = 4294901760 persistent\r\n\
+             OFFSET I64 = -5 (-10000000000..=10000000000) volatile\r\n\
+             UPTIME U64 = 0 persistent\r\n\
+             LEVEL Enum = Debug persistent\r\n"
+        );
+
+        let mut serial = MemSerial::new("reset\nget NUM\nget LEVEL\n");
+        shell.run(&mut db, &mut serial).unwrap();
+        assert_eq!(serial.output(), "OK\r\n15\r\nInfo\r\n");
+    }
+
+    #[test]
+    fn shell_drops_long_lines() {
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_db_with_empty_observer_and_storage();
+        let mut shell = ShoobyShell::<16>::new();
+        let mut out = MemSerial::new("");
+
+        // a line can arrive in pieces
+        shell.process(&mut db, b"get N", &mut out).unwrap();
+        shell.process(&mut db, b"UM\n", &mut out).unwrap();
+        shell
+            .process(&mut db, b"set STRING far too long for the line\n", &mut out)
+            .unwrap();
+        shell.process(&mut db, b"help\n", &mut out).unwrap();
+
+        assert!(out
+            .output()
+            .starts_with("15\r\nerror: line too long\r\ncommands:\r\n"));
+        assert_eq!(
+            db.reader()[TESTER::ID::STRING].get_string().unwrap(),
+            "default"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_map_of_fields() {
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_db_with_empty_observer_and_storage();
+
+        let json = serde_json::to_string(&ShoobyMap(db.reader())).unwrap();
+        assert_eq!(
+            json,
+            r#"{"NUM":15,"STRING":"default","BOOLEAN":false,"BLOB":"0500000009000000","FLOAT":0.5,"MASK":4294901760,"OFFSET":-5,"UPTIME":0,"LEVEL":"Info"}"#
+        );
+
+        let apply = |db: &mut TESTER::DB, json: &str| {
+            let mut errors = Vec::new();
+            let result = apply_map(
+                db,
+                &mut serde_json::Deserializer::from_str(json),
+                |name, err| errors.push(std::format!("{}: {}", name, err)),
+            );
+            (result.map_err(|err| err.to_string()), errors)
+        };
+
+        // what is serialized applies back without changes
+        assert_eq!(apply(&mut db, &json), (Ok(()), vec![]));
+
+        let (result, errors) = apply(
+            &mut db,
+            r#"{"NUM":42,"TESTER::ID::STRING":"from json","LEVEL":"Debug","BLOB":"0100000002000000","UPTIME":18446744073709551615}"#,
+        );
+        assert_eq!((result, errors), (Ok(()), vec![]));
+        let reader = db.reader();
+        assert_eq!(reader[TESTER::ID::NUM].get_int::<i32>().unwrap(), 42);
+        assert_eq!(
+            reader[TESTER::ID::STRING].get_string().unwrap(),
+            "from json"
+        );
+        assert_eq!(
+            reader[TESTER::ID::LEVEL].get_enum::<Level>().unwrap(),
+            Level::Debug
+        );
+        assert_eq!({ reader[TESTER::ID::BLOB].get_blob::<A>().unwrap().b }, 2);
+        assert_eq!(
+            reader[TESTER::ID::UPTIME].get_u64::<u64>().unwrap(),
+            u64::MAX
+        );
+
+        // every bad field is reported, and the good ones are not applied either
+        let (result, errors) = apply(
+            &mut db,
+            r#"{"NUM":500,"MISSING":1,"BOOLEAN":true,"MASK":-1,"STRING":7}"#,
+        );
+        assert_eq!(result.unwrap_err(), "NUM: Out of bounds");
+        assert_eq!(
+            errors,
+            vec![
+                "NUM: Out of bounds",
+                "MISSING: Unknown field",
+                "MASK: Invalid type conversion",
+                "STRING: Invalid type",
+            ]
+        );
+        assert_eq!(db.reader()[TESTER::ID::NUM].get_int::<i32>().unwrap(), 42);
+        assert_eq!(db.reader()[TESTER::ID::BOOLEAN].get_bool().unwrap(), false);
+
+        // a map that can't be parsed changes nothing
+        let (result, _) = apply(&mut db, r#"{"BOOLEAN":true,"NUM":[1]}"#);
+        assert!(result.is_err());
+        assert_eq!(db.reader()[TESTER::ID::BOOLEAN].get_bool().unwrap(), false);
+    }
+
+    #[test]
+    fn json_export_and_import() {
+        create_db_instance!(TESTER);
+        let mut db = TESTER::take_db_with_empty_observer_and_storage();
+
+        let mut json = String::new();
+        write_json(db.reader(), &mut json).unwrap();
+        assert_eq!(
+            json,
+            r#"{"NUM":15,"STRING":"default","BOOLEAN":false,"BLOB":"0500000009000000","FLOAT":0.5,"MASK":4294901760,"OFFSET":"-5","UPTIME":"0","LEVEL":"Info"}"#
+        );
+
+        let mut subset = String::new();
+        let ids: TESTER::IdSet = TESTER::id_set(&[TESTER::ID::LEVEL, TESTER::ID::NUM]);
+        write_json_of(db.reader(), ids.iter(), &mut subset).unwrap();
+        assert_eq!(subset, r#"{"NUM":15,"LEVEL":"Info"}"#);
+
+        let mut errors = Vec::new();
+        let mut apply = |db: &mut TESTER::DB, json: &str| {
+            errors.clear();
+            let result = apply_json(db, json, |name, err| {
+                errors.push(std::format!("{}: {}", name, err))
+            });
+            (result, errors.clone())
+        };
+
+        // what is exported imports back
+        assert!(apply(&mut db, &json).0.is_ok());
+
+        let (result, errors) = apply(
+            &mut db,
+            r#" { "NUM" : 42, "TESTER::ID::STRING": "a \"quote\"\n\u00e9\ud83d\ude00",
+                "BOOLEAN": true, "FLOAT": -2.5e-1, "LEVEL": "Debug", "BLOB": "0100000002000000",
+                "OFFSET": -9000000000, "UPTIME": "18446744073709551615" } "#,
+        );
+        assert!(result.is_ok(), "{:?}", errors);
+        let reader = db.reader();
+        assert_eq!(reader[TESTER::ID::NUM].get_int::<i32>().unwrap(), 42);
+        assert_eq!(
+            reader[TESTER::ID::STRING].get_string().unwrap(),
+            "a \"quote\"\n\u{e9}\u{1f600}"
+        );
+        assert_eq!(reader[TESTER::ID::BOOLEAN].get_bool().unwrap(), true);
+        assert_eq!(reader[TESTER::ID::FLOAT].get_float::<f32>().unwrap(), -0.25);
+        assert_eq!(
+            reader[TESTER::ID::LEVEL].get_enum::<Level>().unwrap(),
+            Level::Debug
+        );
+        assert_eq!({ reader[TESTER::ID::BLOB].get_blob::<A>().unwrap().a }, 1);
+        assert_eq!(
+            reader[TESTER::ID::OFFSET].get_i64::<i64>().unwrap(),
+            -9_000_000_000
+        );
+        assert_eq!(
+            reader[TESTER::ID::UPTIME].get_u64::<u64>().unwrap(),
+            u64::MAX
+        );
+
+        // 64 bit numbers are strings so they keep all their digits
+        let mut json = String::new();
+        write_json_of(db.reader(), [TESTER::ID::UPTIME], &mut json).unwrap();
+        assert_eq!(json, r#"{"UPTIME":"18446744073709551615"}"#);
+
+        // escapes are written back
+        let mut json = String::new();
+        write_json_of(db.reader(), [TESTER::ID::STRING], &mut json).unwrap();
+        assert_eq!(json, "{\"STRING\":\"a \\\"quote\\\"\\n\u{e9}\u{1f600}\"}");
+
+        // every bad field is reported, and the good ones are not applied either
+        let (result, errors) = apply(
+            &mut db,
+            r#"{"NUM":500,"MISSING":1,"BOOLEAN":false,"STRING":7,"MASK":null,"UPTIME":[1,{"a":[]}]}"#,
+        );
+        assert!(matches!(result, Err(ShoobyError::OutOfBounds)));
+        assert_eq!(
+            errors,
+            vec![
+                "NUM: Out of bounds",
+                "MISSING: Unknown field",
+                "STRING: Invalid type",
+                "MASK: Invalid type",
+                "UPTIME: Invalid type",
+            ]
+        );
+        assert_eq!(db.reader()[TESTER::ID::NUM].get_int::<i32>().unwrap(), 42);
+        assert_eq!(db.reader()[TESTER::ID::BOOLEAN].get_bool().unwrap(), true);
+
+        // JSON that can't be parsed changes nothing
+        for bad in [
+            r#"{"BOOLEAN":false"#,
+            r#"{"BOOLEAN":false,}"#,
+            r#"{"BOOLEAN" false}"#,
+            r#"{"BOOLEAN":fals}"#,
+            r#"{"BOOLEAN":false} x"#,
+            r#"["BOOLEAN"]"#,
+            r#"{"STRING":"\x"}"#,
+            r#"{"UPTIME":[1}}"#,
+            r#"{"UPTIME":{"a":[}]}"#,
+        ] {
+            assert!(
+                matches!(apply(&mut db, bad).0, Err(ShoobyError::InvalidFormat)),
+                "{}",
+                bad
+            );
+        }
+        let too_deep = std::format!(r#"{{"MASK":{}{}}}"#, "[".repeat(65), "]".repeat(65));
+        assert!(matches!(
+            apply(&mut db, &too_deep).0,
+            Err(ShoobyError::InvalidFormat)
+        ));
+        assert_eq!(db.reader()[TESTER::ID::BOOLEAN].get_bool().unwrap(), true);
+        assert!(apply(&mut db, "{}").0.is_ok());
+    }
+
+    #[test]
+    fn snapshot_export_and_import() {
+        let mut snapshot = [0u8; 256];
+        let len = {
+            create_db_instance!(TESTER);
+            let mut db = TESTER::take_db_with_empty_observer_and_storage();
+            db.try_write_with(|writer| {
+                writer.field_mut(TESTER::ID::NUM).set_num(42)?;
+                writer.field_mut(TESTER::ID::STRING).set_string("backup")?;
+                writer
+                    .field_mut(TESTER::ID::BLOB)
+                    .set_blob(&A { a: 1, b: 2 })?;
+                writer
+                    .field_mut(TESTER::ID::OFFSET)
+                    .set_i64(-9_000_000_000i64)?;
+                writer
+                    .field_mut(TESTER::ID::LEVEL)
+                    .set_enum(Level::Debug)
+                    .map(|_| ())
+            })
+            .unwrap();
+
+            assert!(matches!(
+                db.export_to(&mut snapshot[..TESTER::SNAPSHOT_SIZE - 1]),
+                Err(ShoobyError::InvalidSize)
+            ));
+            // the name length is one byte
+            let long_name = "N".repeat(256);
+            assert!(matches!(
+                snapshot::export_snapshot(&long_name, db.reader(), &mut [0u8; 1024]),
+                Err(ShoobyError::InvalidSize)
+            ));
+            db.export_to(&mut snapshot).unwrap()
+        };
+        let snapshot = &snapshot[..len];
+        assert_eq!(&snapshot[..6], b"SHBY\x01\x06");
+        assert_eq!(&snapshot[6..12], b"TESTER");
+
+        // restored on another device
+        {
+            create_db_instance!(TESTER);
+            assert_eq!(len, TESTER::SNAPSHOT_SIZE);
+            let mut db = TESTER::take_db_with_empty_observer_and_storage();
+            db.import_from(snapshot).unwrap();
+            let reader = db.reader();
+            assert_eq!(reader[TESTER::ID::NUM].get_int::<i32>().unwrap(), 42);
+            assert_eq!(reader[TESTER::ID::STRING].get_string().unwrap(), "backup");
+            assert_eq!({ reader[TESTER::ID::BLOB].get_blob::<A>().unwrap().b }, 2);
+            assert_eq!(
+                reader[TESTER::ID::OFFSET].get_i64::<i64>().unwrap(),
+                -9_000_000_000
+            );
+            assert_eq!(
+                reader[TESTER::ID::LEVEL].get_enum::<Level>().unwrap(),
+                Level::Debug
+            );
+
+            // NUM is the first record, its value follows the name, schema hash, count and record header
+            let num_offset = 12 + 6 + 7;
+            let mut damaged = snapshot.to_vec();
+            damaged[num_offset] = 43;
+            assert!(matches!(
+                db.import_from(&damaged),
+                Err(ShoobyError::CorruptedRecord)
+            ));
+            assert!(matches!(
+                db.import_from(&snapshot[..3]),
+                Err(ShoobyError::InvalidFormat)
+            ));
+
+            // a valid snapshot with a value out of range changes nothing
+            let mut out_of_range = snapshot.to_vec();
+            out_of_range[num_offset] = 200;
+            db.write_with(|writer| {
+                writer
+                    .field_mut(TESTER::ID::BOOLEAN)
+                    .set_bool(true)
+                    .unwrap();
+            });
+            let reseal = |snapshot: &mut Vec<u8>| {
+                let body = len - 4;
+                let crc = !crate::utils::crc32_update(crate::utils::CRC32_INIT, &snapshot[..body]);
+                snapshot[body..].copy_from_slice(&crc.to_le_bytes());
+            };
+            reseal(&mut out_of_range);
+            assert!(matches!(
+                db.import_from(&out_of_range),
+                Err(ShoobyError::OutOfBounds)
+            ));
+            assert_eq!(db.reader()[TESTER::ID::BOOLEAN].get_bool().unwrap(), true);
+
+            // a record of no field or of another type is an error of that record
+            let mut unknown = snapshot.to_vec();
+            unknown[num_offset - 7] ^= 0xFF;
+            reseal(&mut unknown);
+            assert!(matches!(
+                db.import_from(&unknown),
+                Err(ShoobyError::UnknownField)
+            ));
+            let mut retyped = snapshot.to_vec();
+            retyped[num_offset - 3] = db.reader()[TESTER::ID::BOOLEAN].type_tag();
+            reseal(&mut retyped);
+            assert!(matches!(
+                db.import_from(&retyped),
+                Err(ShoobyError::InvalidType)
+            ));
+            assert_eq!(db.reader()[TESTER::ID::BOOLEAN].get_bool().unwrap(), true);
+        }
+
+        // another DB or the same DB with other fields rejects it
+        {
+            create_db_instance!(OTHER);
+            let mut db = OTHER::take_db_with_empty_observer_and_storage();
+            assert!(matches!(
+                db.import_from(snapshot),
+                Err(ShoobyError::LayoutChanged)
+            ));
+        }
+        shooby_db!(TESTER =>
+            {NUM, Int, 15, Some((10, 100)), NON_PERSISTENT},
+            {STRING, String, "default", 32, NON_PERSISTENT},
+        );
+        let mut db = TESTER::take_db_with_empty_observer_and_storage();
+        assert!(matches!(
+            db.import_from(snapshot),
+            Err(ShoobyError::LayoutChanged)
+        ));
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        use crate::utils::{crc32_update, CRC32_INIT};
+        assert_eq!(!crc32_update(CRC32_INIT, b"123456789"), 0xcbf4_3926);
+    }
+}
+
+/// Uses a DB the way a no_std firmware does, nothing from std is in scope here.
+/// Runs with `cargo test --no-default-features --lib`
+#[cfg(all(test, not(feature = "std")))]
+mod no_std_tests {
+    use super::*;
+    use core::cell::Cell;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Mode {
+        Off = 0,
+        On = 1,
+    }
+
+    impl ShoobyEnum for Mode {
+        const VARIANTS: &'static [(u32, &'static str)] = &[(0, "Off"), (1, "On")];
+
+        fn to_discriminant(&self) -> u32 {
+            *self as u32
+        }
+
+        fn from_discriminant(discriminant: u32) -> Option<Self> {
+            match discriminant {
+                0 => Some(Mode::Off),
+                1 => Some(Mode::On),
+                _ => None,
+            }
+        }
+    }
+
+    shooby_db!(FIRMWARE =>
+        {NUM, Int, 15, Some((10, 100)), PERSISTENT},
+        {NAME, String, "device", 16, PERSISTENT},
+        {ENABLED, Bool, false, None, NON_PERSISTENT},
+        {BLOB, Blob, A {a: 5, b: 9}, core::mem::size_of::<A>(), PERSISTENT},
+        {GAIN, Float, 0.5, Some((-1.0, 1.0)), PERSISTENT},
+        {MASK, U32, 0xFFFF_0000, None, PERSISTENT},
+        {OFFSET, I64, -5, None, PERSISTENT},
+        {UPTIME, U64, 0, None, NON_PERSISTENT},
+        {MODE, Enum(Mode), Mode::Off, None, PERSISTENT},
+    );
+
+    struct Counter {
+        changes: Cell<usize>,
+    }
+
+    impl ShoobyObserver for Counter {
+        type ID = FIRMWARE::ID;
+
+        fn update(&self, _field: &ShoobyField<Self::ID>) {
+            self.changes.set(self.changes.get() + 1);
+        }
+    }
+
+    #[test]
+    fn db_works_without_std() {
+        use FIRMWARE::Reader;
+
+        let observer = Counter {
+            changes: Cell::new(0),
+        };
+        let storage = FlashStorage::new(RamFlash::<256, 4>::new()).unwrap();
+        let mut db = FIRMWARE::DB::take(Some(observer), Some(storage));
+        db.init().unwrap();
+
+        db.try_write_with(|writer| {
+            writer.field_mut(FIRMWARE::ID::NUM).set_num(42)?;
+            writer.field_mut(FIRMWARE::ID::NAME).set_string("sensor")?;
+            writer
+                .field_mut(FIRMWARE::ID::MODE)
+                .set_enum(Mode::On)
+                .map(|_| ())
+        })
+        .unwrap();
+        assert!(db
+            .try_write_with(|writer| writer.field_mut(FIRMWARE::ID::NUM).set_num(500))
+            .is_err());
+
+        assert_eq!(db.reader().num(), 42);
+        assert_eq!(db.reader().name(), "sensor");
+        assert_eq!(db.reader().mode(), Mode::On);
+        db.observer(|observer| assert_eq!(observer.unwrap().changes.get(), 3));
+
+        let mut json = heapless::String::<256>::new();
+        write_json(db.reader(), &mut json).unwrap();
+        assert!(json.starts_with(r#"{"NUM":42,"NAME":"sensor""#));
+
+        #[cfg(feature = "critical-section")]
+        let mut db = {
+            let shared = SharedDB::new(db);
+            shared.write_with(|writer| {
+                writer
+                    .field_mut(FIRMWARE::ID::ENABLED)
+                    .set_bool(true)
+                    .unwrap();
+            });
+            assert!(shared.reader().enabled());
+            shared.into_inner()
+        };
+
+        db.factory_reset().unwrap();
+        assert_eq!(db.reader().num(), 15);
     }
 }
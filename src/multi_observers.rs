@@ -1,32 +1,273 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
 use crate::errors::ShoobyError;
 use crate::traits::*;
-use crate::ShoobyField;
+use crate::{ShoobyChange, ShoobyField};
 use heapless::Vec as HeaplessVec;
 
+/// Holds up to N observers of the same type, without heap.
+/// They are notified in the order they were added
 pub struct MultiObserver<ID, T: ShoobyObserver<ID = ID>, const N: usize> {
-    observers: HeaplessVec<T, N>,
+    observers: HandleList<T, N>,
 }
 
 impl<ID, T: ShoobyObserver<ID = ID>, const N: usize> MultiObserver<ID, T, N> {
     pub fn new() -> Self {
         Self {
-            observers: HeaplessVec::new(),
+            observers: HandleList::new(),
         }
     }
 
-    pub fn add(&mut self, observer: T) -> Result<(), ShoobyError> {
+    /// Adds an observer, returns OutOfBounds if there is no room for it
+    pub fn add(&mut self, observer: T) -> Result<ObserverHandle, ShoobyError> {
+        self.observers.add(observer)
+    }
+
+    /// Removes the observer of the handle and frees its place for another observer.
+    /// Returns the observer, or None if it was already removed
+    pub fn remove(&mut self, handle: ObserverHandle) -> Option<T> {
+        self.observers.remove(handle)
+    }
+
+    /// Removes the observer of the handle through a shared reference, so an observer can remove itself
+    /// or another one while it is notified. See HandleList for when its place is freed.
+    /// Returns false if it was already removed
+    pub fn unsubscribe(&self, handle: ObserverHandle) -> bool {
+        self.observers.unsubscribe(handle)
+    }
+
+    /// The number of observers
+    pub fn len(&self) -> usize {
+        self.observers.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<ID, T: ShoobyObserver<ID = ID>, const N: usize> Default for MultiObserver<ID, T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<ID: AsRef<str> + Copy + 'static, T: ShoobyObserver<ID = ID>, const N: usize> ShoobyObserver
+    for MultiObserver<ID, T, N>
+{
+    type ID = ID;
+
+    fn is_interested(&self, id: &Self::ID) -> bool {
         self.observers
-            .push(observer)
-            .map_err(|_| ShoobyError::OutOfBounds)
+            .iter()
+            .any(|observer| observer.is_interested(id))
+    }
+
+    fn update(&self, item: &ShoobyField<Self::ID>) {
+        for observer in self.observers.iter() {
+            if observer.is_interested(&item.id()) {
+                observer.update(item);
+            }
+        }
     }
+
+    fn on_change(&self, change: &ShoobyChange<'_, Self::ID>) {
+        for observer in self.observers.iter() {
+            if observer.is_interested(&change.id) {
+                observer.on_change(change);
+            }
+        }
+    }
+}
+
+/// Identifies an observer added to a multi observer, used to remove it.
+/// A handle is never given twice (until 2^32 observers were added), so an old handle can't remove a new observer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObserverHandle(u32);
+
+struct Entry<T> {
+    handle: ObserverHandle,
+    item: T,
+    /// Set by unsubscribe, the entry is skipped until its place is freed
+    removed: AtomicBool,
+}
+
+/// Observers in the order they were added, each with a unique handle.
+/// An observer unsubscribed during a notification is not notified from then on, the others are still
+/// notified once. The place of an unsubscribed observer is freed by the next add or remove
+struct HandleList<T, const N: usize> {
+    entries: HeaplessVec<Entry<T>, N>,
+    next_handle: u32,
 }
 
-impl<ID, T: ShoobyObserver<ID = ID>, const N: usize> ShoobyObserver for MultiObserver<ID, T, N> {
+impl<T, const N: usize> HandleList<T, N> {
+    const fn new() -> Self {
+        Self {
+            entries: HeaplessVec::new(),
+            next_handle: 0,
+        }
+    }
+
+    fn add(&mut self, item: T) -> Result<ObserverHandle, ShoobyError> {
+        self.free_unsubscribed();
+        let handle = ObserverHandle(self.next_handle);
+        self.entries
+            .push(Entry {
+                handle,
+                item,
+                removed: AtomicBool::new(false),
+            })
+            .map_err(|_| ShoobyError::OutOfBounds)?;
+        self.next_handle = self.next_handle.wrapping_add(1);
+        Ok(handle)
+    }
+
+    /// Removes the item and keeps the order of the others
+    fn remove(&mut self, handle: ObserverHandle) -> Option<T> {
+        self.free_unsubscribed();
+        let index = self
+            .entries
+            .iter()
+            .position(|entry| entry.handle == handle)?;
+        Some(self.entries.remove(index).item)
+    }
+
+    fn unsubscribe(&self, handle: ObserverHandle) -> bool {
+        self.entries
+            .iter()
+            .find(|entry| entry.handle == handle)
+            .is_some_and(|entry| !entry.removed.swap(true, Ordering::Relaxed))
+    }
+
+    fn free_unsubscribed(&mut self) {
+        self.entries.retain_mut(|entry| !*entry.removed.get_mut());
+    }
+
+    /// The items that were not unsubscribed, checked as the iteration goes
+    fn iter(&self) -> impl Iterator<Item = &T> {
+        self.entries
+            .iter()
+            .filter(|entry| !entry.removed.load(Ordering::Relaxed))
+            .map(|entry| &entry.item)
+    }
+}
+
+/// Holds up to N observers of different types by reference, without heap.
+/// They are notified in the order they were added
+///     `let mut observers = DynMultiObserver::<NAME::ID, 4>::new(); let handle = observers.add(&logger)?;`
+pub struct DynMultiObserver<'a, ID: 'static, const N: usize> {
+    observers: HandleList<&'a dyn ShoobyObserver<ID = ID>, N>,
+}
+
+impl<'a, ID, const N: usize> DynMultiObserver<'a, ID, N> {
+    pub const fn new() -> Self {
+        Self {
+            observers: HandleList::new(),
+        }
+    }
+
+    /// Adds an observer, returns OutOfBounds if there is no room for it
+    pub fn add(
+        &mut self,
+        observer: &'a dyn ShoobyObserver<ID = ID>,
+    ) -> Result<ObserverHandle, ShoobyError> {
+        self.observers.add(observer)
+    }
+
+    /// Removes the observer of the handle, returns None if it was already removed
+    pub fn remove(&mut self, handle: ObserverHandle) -> Option<&'a dyn ShoobyObserver<ID = ID>> {
+        self.observers.remove(handle)
+    }
+
+    /// Same as MultiObserver::unsubscribe
+    pub fn unsubscribe(&self, handle: ObserverHandle) -> bool {
+        self.observers.unsubscribe(handle)
+    }
+}
+
+impl<ID, const N: usize> Default for DynMultiObserver<'_, ID, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<ID: AsRef<str> + Copy + 'static, const N: usize> ShoobyObserver
+    for DynMultiObserver<'_, ID, N>
+{
     type ID = ID;
 
+    fn is_interested(&self, id: &Self::ID) -> bool {
+        self.observers
+            .iter()
+            .any(|observer| observer.is_interested(id))
+    }
+
     fn update(&self, item: &ShoobyField<Self::ID>) {
         for observer in self.observers.iter() {
-            observer.update(item);
+            if observer.is_interested(&item.id()) {
+                observer.update(item);
+            }
+        }
+    }
+
+    fn on_change(&self, change: &ShoobyChange<'_, Self::ID>) {
+        for observer in self.observers.iter() {
+            if observer.is_interested(&change.id) {
+                observer.on_change(change);
+            }
+        }
+    }
+}
+
+/// A callback of FnMultiObserver, a fn or a closure
+pub type ShoobyCallback<'a, ID> = &'a dyn Fn(&ShoobyChange<'_, ID>);
+
+/// Holds up to N callbacks by reference, without heap. Every callback gets all the changes,
+/// in the order the callbacks were added
+///     `let on_change = |change: &ShoobyChange<'_, NAME::ID>| println!("{}", change.new);`
+///     `let mut observers = FnMultiObserver::<NAME::ID, 4>::new(); let handle = observers.add(&on_change)?;`
+pub struct FnMultiObserver<'a, ID: 'static, const N: usize> {
+    callbacks: HandleList<ShoobyCallback<'a, ID>, N>,
+}
+
+impl<'a, ID, const N: usize> FnMultiObserver<'a, ID, N> {
+    pub const fn new() -> Self {
+        Self {
+            callbacks: HandleList::new(),
+        }
+    }
+
+    /// Adds a callback, returns OutOfBounds if there is no room for it
+    pub fn add(&mut self, callback: ShoobyCallback<'a, ID>) -> Result<ObserverHandle, ShoobyError> {
+        self.callbacks.add(callback)
+    }
+
+    /// Removes the callback of the handle, returns None if it was already removed
+    pub fn remove(&mut self, handle: ObserverHandle) -> Option<ShoobyCallback<'a, ID>> {
+        self.callbacks.remove(handle)
+    }
+
+    /// Same as MultiObserver::unsubscribe, a callback can remove itself or another one
+    pub fn unsubscribe(&self, handle: ObserverHandle) -> bool {
+        self.callbacks.unsubscribe(handle)
+    }
+}
+
+impl<ID, const N: usize> Default for FnMultiObserver<'_, ID, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<ID: 'static, const N: usize> ShoobyObserver for FnMultiObserver<'_, ID, N> {
+    type ID = ID;
+
+    /// The callbacks get the whole change, see on_change
+    fn update(&self, _item: &ShoobyField<Self::ID>) {}
+
+    fn on_change(&self, change: &ShoobyChange<'_, Self::ID>) {
+        for callback in self.callbacks.iter() {
+            callback(change);
         }
     }
 }
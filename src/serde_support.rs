@@ -0,0 +1,247 @@
+//! serde support, enabled by the `serde` feature.
+//!
+//! The fields of a DB are a map of short field name to value. Numbers and bools are plain values,
+//! enums are the variant name and blobs are hex text in human readable formats or bytes in binary ones.
+//! Serialize with ShoobyMap, deserialize into a DB with apply_map.
+
+use core::fmt::{Formatter, Result as FmtResult};
+
+use heapless::String as HeaplessString;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+use crate::errors::ShoobyError;
+use crate::shooby_field::{ShoobyField, ShoobyKind, ShoobyValue};
+use crate::traits::{ShoobyDb, ShoobyId};
+use crate::writer::{FieldWriter, ShoobyWriter};
+
+impl Serialize for ShoobyValue<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            ShoobyValue::Bool(val) => serializer.serialize_bool(val),
+            ShoobyValue::Int(val) => serializer.serialize_i32(val),
+            ShoobyValue::U32(val) => serializer.serialize_u32(val),
+            ShoobyValue::I64(val) => serializer.serialize_i64(val),
+            ShoobyValue::U64(val) => serializer.serialize_u64(val),
+            ShoobyValue::Float(val) => serializer.serialize_f32(val),
+            ShoobyValue::Enum(_, name) => serializer.serialize_str(name),
+            ShoobyValue::String(val) => serializer.serialize_str(val),
+            ShoobyValue::Blob(_) if serializer.is_human_readable() => serializer.collect_str(self),
+            ShoobyValue::Blob(data) => serializer.serialize_bytes(data),
+        }
+    }
+}
+
+/// The fields of a DB as a map of short field name to value, for serde
+///     `let json = serde_json::to_string(&ShoobyMap(db.reader()))?;`
+pub struct ShoobyMap<'a, ID: 'static>(pub &'a [ShoobyField<ID>]);
+
+impl<ID: AsRef<str> + Copy> Serialize for ShoobyMap<'_, ID> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for field in self.0.iter() {
+            map.serialize_entry(field.short_name(), &field.value())?;
+        }
+        map.end()
+    }
+}
+
+/// Applies a map of field names to values to the DB in one write, through the validating setters.
+/// Every field that is unknown or can't get its value is passed to on_error, and then nothing
+/// is changed and the returned error names the first of them, like `NUM: Out of bounds`.
+/// A map that can't be parsed returns the error of the deserializer, and nothing is changed as well
+///     `apply_map(&mut db, &mut serde_json::Deserializer::from_str(json), |name, err| println!("{}: {}", name, err))?;`
+pub fn apply_map<'de, DB, D, F>(db: &mut DB, deserializer: D, on_error: F) -> Result<(), D::Error>
+where
+    DB: ShoobyDb,
+    D: Deserializer<'de>,
+    F: FnMut(&str, ShoobyError),
+{
+    let result = db.try_write_with(|writer| {
+        let rejected = deserializer
+            .deserialize_map(MapVisitor {
+                writer,
+                rejected: Rejected {
+                    on_error,
+                    first: None,
+                },
+            })
+            .map_err(ApplyError::Format)?;
+
+        match rejected {
+            None => Ok(()),
+            Some((name, err)) => Err(ApplyError::Rejected(name, err)),
+        }
+    });
+
+    result.map_err(|err| match err {
+        ApplyError::Format(err) => err,
+        ApplyError::Rejected(name, err) => de::Error::custom(format_args!("{}: {}", name, err)),
+        ApplyError::Storage(err) => de::Error::custom(err),
+    })
+}
+
+/// The longest name of a rejected field kept for the error of apply_map, a longer unknown name is cut
+const REJECTED_NAME_MAX_SIZE: usize = 64;
+
+type RejectedName = HeaplessString<REJECTED_NAME_MAX_SIZE>;
+
+enum ApplyError<E> {
+    Format(E),
+    Rejected(RejectedName, ShoobyError),
+    Storage(ShoobyError),
+}
+
+impl<E> From<ShoobyError> for ApplyError<E> {
+    fn from(err: ShoobyError) -> Self {
+        ApplyError::Storage(err)
+    }
+}
+
+/// Applies the entries to the fields
+struct MapVisitor<'a, 'b, ID: ShoobyId, F> {
+    writer: &'a mut ShoobyWriter<'b, ID>,
+    rejected: Rejected<F>,
+}
+
+/// Passes every rejected field to on_error and keeps the first one
+struct Rejected<F> {
+    on_error: F,
+    first: Option<(RejectedName, ShoobyError)>,
+}
+
+impl<F: FnMut(&str, ShoobyError)> Rejected<F> {
+    fn reject(&mut self, name: &str, err: ShoobyError) {
+        (self.on_error)(name, err.clone());
+        self.first.get_or_insert_with(|| {
+            let mut first = RejectedName::new();
+            let _ = name.chars().try_for_each(|c| first.push(c));
+            (first, err)
+        });
+    }
+}
+
+impl<'de, ID: ShoobyId, F: FnMut(&str, ShoobyError)> Visitor<'de> for MapVisitor<'_, '_, ID, F> {
+    type Value = Option<(RejectedName, ShoobyError)>;
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("a map of field names to values")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<Self::Value, A::Error> {
+        while let Some(id) = map.next_key_seed(NameSeed(&mut self))? {
+            let Some(id) = id else {
+                map.next_value::<IgnoredAny>()?;
+                continue;
+            };
+
+            if let Err(err) = map.next_value_seed(ValueSeed(self.writer.field_mut(id)))? {
+                self.rejected
+                    .reject(self.writer[id.index()].short_name(), err);
+            }
+        }
+        Ok(self.rejected.first)
+    }
+}
+
+/// Resolves a field name to its ID, reports unknown names while the name is still borrowed
+struct NameSeed<'a, 'b, 'c, ID: ShoobyId, F>(&'a mut MapVisitor<'b, 'c, ID, F>);
+
+impl<'de, ID: ShoobyId, F: FnMut(&str, ShoobyError)> DeserializeSeed<'de>
+    for NameSeed<'_, '_, '_, ID, F>
+{
+    type Value = Option<ID>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Option<ID>, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de, ID: ShoobyId, F: FnMut(&str, ShoobyError)> Visitor<'de> for NameSeed<'_, '_, '_, ID, F> {
+    type Value = Option<ID>;
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("a field name")
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<Option<ID>, E> {
+        let id = ID::from_name(name);
+        if id.is_none() {
+            self.0.rejected.reject(name, ShoobyError::UnknownField);
+        }
+        Ok(id)
+    }
+}
+
+/// Sets a field from a value of the format, the setter result is the value of the seed
+struct ValueSeed<'a, ID: 'static>(FieldWriter<'a, ID>);
+
+impl<'de, ID: AsRef<str> + Copy> DeserializeSeed<'de> for ValueSeed<'_, ID> {
+    type Value = Result<(), ShoobyError>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        // text formats describe their values, so a value of the wrong type is a field error and not
+        // a format error. Binary formats may need the type of the field to read the value
+        if deserializer.is_human_readable() {
+            return deserializer.deserialize_any(self);
+        }
+
+        match self.0.kind() {
+            ShoobyKind::Bool => deserializer.deserialize_bool(self),
+            ShoobyKind::Int => deserializer.deserialize_i32(self),
+            ShoobyKind::U32 => deserializer.deserialize_u32(self),
+            ShoobyKind::I64 => deserializer.deserialize_i64(self),
+            ShoobyKind::U64 => deserializer.deserialize_u64(self),
+            ShoobyKind::Float => deserializer.deserialize_f32(self),
+            ShoobyKind::Enum | ShoobyKind::String => deserializer.deserialize_str(self),
+            ShoobyKind::Blob => deserializer.deserialize_bytes(self),
+        }
+    }
+}
+
+impl<'de, ID: AsRef<str> + Copy> Visitor<'de> for ValueSeed<'_, ID> {
+    type Value = Result<(), ShoobyError>;
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "a value for {}", self.0.short_name())
+    }
+
+    fn visit_bool<E: de::Error>(mut self, value: bool) -> Result<Self::Value, E> {
+        Ok(self.0.set_bool(value).map(|_| ()))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        let mut field = self.0;
+        Ok(match field.kind() {
+            ShoobyKind::Int => field.set_num(value).map(|_| ()),
+            ShoobyKind::U32 => field.set_u32(value).map(|_| ()),
+            ShoobyKind::I64 => field.set_i64(value).map(|_| ()),
+            ShoobyKind::U64 => field.set_u64(value).map(|_| ()),
+            ShoobyKind::Float => field.set_float(value as f32).map(|_| ()),
+            ShoobyKind::Enum => u32::try_from(value)
+                .map_err(|_| ShoobyError::OutOfBounds)
+                .and_then(|value| field.set_discriminant(value)),
+            _ => Err(ShoobyError::InvalidType),
+        })
+    }
+
+    fn visit_u64<E: de::Error>(mut self, value: u64) -> Result<Self::Value, E> {
+        match i64::try_from(value) {
+            Ok(value) => self.visit_i64(value),
+            Err(_) => Ok(self.0.set_u64(value).map(|_| ())),
+        }
+    }
+
+    fn visit_f64<E: de::Error>(mut self, value: f64) -> Result<Self::Value, E> {
+        Ok(self.0.set_float(value as f32).map(|_| ()))
+    }
+
+    /// Strings, enum names, blobs in hex and numbers in text
+    fn visit_str<E: de::Error>(mut self, value: &str) -> Result<Self::Value, E> {
+        Ok(self.0.set_from_str(value))
+    }
+
+    fn visit_bytes<E: de::Error>(mut self, value: &[u8]) -> Result<Self::Value, E> {
+        Ok(self.0.set_blob_bytes(value))
+    }
+}
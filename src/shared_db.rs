@@ -0,0 +1,240 @@
+//! A DB that can be shared between threads or between the main loop and interrupts.
+//!
+//! Many readers can hold a reader guard at the same time, writes are exclusive.
+//! With the `std` feature the lock is a std::sync::RwLock. Without it the `critical-section` feature is needed,
+//! the lock state is then kept behind a critical section and waiting is done by spinning, so a reader guard
+//! should never be held while an interrupt may try to write (use try_reader/try_write_with_now there instead).
+
+use core::ops::Deref;
+
+use crate::errors::ShoobyError;
+use crate::shooby_field::ShoobyField;
+use crate::traits::ShoobyDb;
+use crate::writer::ShoobyWriter;
+
+#[cfg(feature = "std")]
+mod lock {
+    use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+
+    pub(super) struct Lock<T>(RwLock<T>);
+    pub(super) type ReadGuard<'a, T> = RwLockReadGuard<'a, T>;
+    pub(super) type WriteGuard<'a, T> = RwLockWriteGuard<'a, T>;
+
+    // a closure that panics is rolled back by its writer, so a lock it poisoned still holds the values
+    // from before that write
+    impl<T> Lock<T> {
+        pub(super) fn new(value: T) -> Self {
+            Self(RwLock::new(value))
+        }
+
+        pub(super) fn read(&self) -> ReadGuard<'_, T> {
+            self.0.read().unwrap_or_else(PoisonError::into_inner)
+        }
+
+        pub(super) fn try_read(&self) -> Option<ReadGuard<'_, T>> {
+            match self.0.try_read() {
+                Ok(guard) => Some(guard),
+                Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+                Err(TryLockError::WouldBlock) => None,
+            }
+        }
+
+        pub(super) fn write(&self) -> WriteGuard<'_, T> {
+            self.0.write().unwrap_or_else(PoisonError::into_inner)
+        }
+
+        pub(super) fn try_write(&self) -> Option<WriteGuard<'_, T>> {
+            match self.0.try_write() {
+                Ok(guard) => Some(guard),
+                Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+                Err(TryLockError::WouldBlock) => None,
+            }
+        }
+
+        pub(super) fn into_inner(self) -> T {
+            self.0.into_inner().unwrap_or_else(PoisonError::into_inner)
+        }
+    }
+}
+
+#[cfg(all(not(feature = "std"), feature = "critical-section"))]
+mod lock {
+    use core::cell::{Cell, UnsafeCell};
+    use core::ops::{Deref, DerefMut};
+
+    // number of readers, or WRITER when written
+    const WRITER: usize = usize::MAX;
+
+    pub(super) struct Lock<T> {
+        state: critical_section::Mutex<Cell<usize>>,
+        value: UnsafeCell<T>,
+    }
+
+    // the state makes sure there is one writer or only readers
+    unsafe impl<T: Send + Sync> Sync for Lock<T> {}
+
+    pub(super) struct ReadGuard<'a, T>(&'a Lock<T>);
+    pub(super) struct WriteGuard<'a, T>(&'a Lock<T>);
+
+    impl<T> Lock<T> {
+        pub(super) fn new(value: T) -> Self {
+            Self {
+                state: critical_section::Mutex::new(Cell::new(0)),
+                value: UnsafeCell::new(value),
+            }
+        }
+
+        /// Changes the state if allowed, returns if it was changed
+        fn update(&self, f: impl Fn(usize) -> Option<usize>) -> bool {
+            critical_section::with(|cs| {
+                let state = self.state.borrow(cs);
+                match f(state.get()) {
+                    Some(new_state) => {
+                        state.set(new_state);
+                        true
+                    }
+                    None => false,
+                }
+            })
+        }
+
+        fn lock_read(&self) -> bool {
+            self.update(|readers| (readers < WRITER - 1).then(|| readers + 1))
+        }
+
+        fn lock_write(&self) -> bool {
+            self.update(|readers| (readers == 0).then_some(WRITER))
+        }
+
+        pub(super) fn read(&self) -> ReadGuard<'_, T> {
+            while !self.lock_read() {
+                core::hint::spin_loop();
+            }
+            ReadGuard(self)
+        }
+
+        pub(super) fn try_read(&self) -> Option<ReadGuard<'_, T>> {
+            self.lock_read().then(|| ReadGuard(self))
+        }
+
+        pub(super) fn write(&self) -> WriteGuard<'_, T> {
+            while !self.lock_write() {
+                core::hint::spin_loop();
+            }
+            WriteGuard(self)
+        }
+
+        pub(super) fn try_write(&self) -> Option<WriteGuard<'_, T>> {
+            self.lock_write().then(|| WriteGuard(self))
+        }
+
+        pub(super) fn into_inner(self) -> T {
+            self.value.into_inner()
+        }
+    }
+
+    impl<T> Deref for ReadGuard<'_, T> {
+        type Target = T;
+
+        fn deref(&self) -> &T {
+            unsafe { &*self.0.value.get() }
+        }
+    }
+
+    impl<T> Drop for ReadGuard<'_, T> {
+        fn drop(&mut self) {
+            self.0.update(|readers| Some(readers - 1));
+        }
+    }
+
+    impl<T> Deref for WriteGuard<'_, T> {
+        type Target = T;
+
+        fn deref(&self) -> &T {
+            unsafe { &*self.0.value.get() }
+        }
+    }
+
+    impl<T> DerefMut for WriteGuard<'_, T> {
+        fn deref_mut(&mut self) -> &mut T {
+            unsafe { &mut *self.0.value.get() }
+        }
+    }
+
+    impl<T> Drop for WriteGuard<'_, T> {
+        fn drop(&mut self) {
+            self.0.update(|_| Some(0));
+        }
+    }
+}
+
+/// Wraps a DB generated by shooby_db! so it can be used from many threads at once.
+/// The DB should be initialized before it is shared
+///     `let shared = SharedDB::new(db); let ssid = shared.reader()[NAME::ID::SSID].get_string()?;`
+pub struct SharedDB<DB> {
+    db: lock::Lock<DB>,
+}
+
+/// Read access to the fields of a SharedDB, writers wait until it is dropped
+pub struct SharedReader<'a, DB: ShoobyDb> {
+    guard: lock::ReadGuard<'a, DB>,
+}
+
+impl<DB: ShoobyDb> Deref for SharedReader<'_, DB> {
+    type Target = [ShoobyField<DB::ID>];
+
+    fn deref(&self) -> &Self::Target {
+        self.guard.reader()
+    }
+}
+
+impl<DB: ShoobyDb> SharedDB<DB> {
+    pub fn new(db: DB) -> Self {
+        Self {
+            db: lock::Lock::new(db),
+        }
+    }
+
+    /// Get the DB array of fields to read from, waits while a write is in progress
+    pub fn reader(&self) -> SharedReader<'_, DB> {
+        SharedReader {
+            guard: self.db.read(),
+        }
+    }
+
+    /// Same as reader, but returns None instead of waiting for a write to end
+    pub fn try_reader(&self) -> Option<SharedReader<'_, DB>> {
+        self.db.try_read().map(|guard| SharedReader { guard })
+    }
+
+    /// Same as DB::write_with, waits for all readers and writers to end
+    pub fn write_with<F>(&self, f: F)
+    where
+        F: FnOnce(&mut ShoobyWriter<'_, DB::ID>),
+    {
+        self.db.write().write_with(f)
+    }
+
+    /// Same as DB::try_write_with, waits for all readers and writers to end
+    pub fn try_write_with<T, E, F>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut ShoobyWriter<'_, DB::ID>) -> Result<T, E>,
+        E: From<ShoobyError>,
+    {
+        self.db.write().try_write_with(f)
+    }
+
+    /// Same as try_write_with, but returns None instead of waiting for readers and writers to end
+    pub fn try_write_with_now<T, E, F>(&self, f: F) -> Option<Result<T, E>>
+    where
+        F: FnOnce(&mut ShoobyWriter<'_, DB::ID>) -> Result<T, E>,
+        E: From<ShoobyError>,
+    {
+        self.db.try_write().map(|mut guard| guard.try_write_with(f))
+    }
+
+    /// Gives back the DB
+    pub fn into_inner(self) -> DB {
+        self.db.into_inner()
+    }
+}
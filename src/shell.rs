@@ -0,0 +1,191 @@
+//! A line based command shell for any DB generated by shooby_db!, over embedded-io read/write.
+//!
+//! Commands:
+//!   help                 the list of commands
+//!   list                 every field with its type, value, range and persistency
+//!   get NAME             the value of a field
+//!   set NAME VALUE       sets a field from text, the value is the rest of the line
+//!   reset                factory reset, all fields get their defaults and are saved
+//! Field names can be short (`SSID`) or qualified (`NAME::ID::SSID`), values use the format of
+//! ShoobyField::set_from_str. Lines end with `\n` or `\r`, no heap is used.
+
+use core::fmt::Write as FmtWrite;
+
+use embedded_io::{Read, Write};
+use heapless::Vec as HeaplessVec;
+
+use crate::errors::ShoobyError;
+use crate::shooby_field::{ShoobyField, ShoobyKind, ShoobyRange};
+use crate::traits::{ShoobyDb, ShoobyFieldsByName, ShoobyId};
+
+const HELP: &str = "commands:\r\n  \
+    help\r\n  \
+    list\r\n  \
+    get NAME\r\n  \
+    set NAME VALUE\r\n  \
+    reset\r\n";
+
+/// Runs shell commands on a DB, LINE is the longest command line that can be received.
+/// Feed it the bytes received from a UART with process, or let it read them with run
+///     `let mut shell = ShoobyShell::<128>::new(); shell.run(&mut db, &mut uart)?;`
+pub struct ShoobyShell<const LINE: usize> {
+    line: HeaplessVec<u8, LINE>,
+    overflow: bool,
+}
+
+impl<const LINE: usize> ShoobyShell<LINE> {
+    pub const fn new() -> Self {
+        Self {
+            line: HeaplessVec::new(),
+            overflow: false,
+        }
+    }
+
+    /// Reads commands from io and writes their output back, until io has nothing more to read
+    pub fn run<DB, IO>(&mut self, db: &mut DB, io: &mut IO) -> Result<(), IO::Error>
+    where
+        DB: ShoobyDb,
+        IO: Read + Write,
+    {
+        let mut buffer = [0u8; 32];
+        loop {
+            let len = io.read(&mut buffer)?;
+            if len == 0 {
+                return io.flush();
+            }
+            self.process(db, &buffer[..len], io)?;
+        }
+    }
+
+    /// Collects received bytes and executes every line they complete.
+    /// A line longer than LINE is dropped with an error
+    pub fn process<DB, W>(&mut self, db: &mut DB, bytes: &[u8], out: &mut W) -> Result<(), W::Error>
+    where
+        DB: ShoobyDb,
+        W: Write,
+    {
+        for &byte in bytes {
+            if byte != b'\n' && byte != b'\r' {
+                self.overflow |= self.line.push(byte).is_err();
+                continue;
+            }
+
+            if self.overflow {
+                out.write_all(b"error: line too long\r\n")?;
+            } else if let Ok(line) = core::str::from_utf8(&self.line) {
+                Self::execute(db, line, out)?;
+            } else {
+                out.write_all(b"error: invalid text\r\n")?;
+            }
+            self.line.clear();
+            self.overflow = false;
+        }
+        Ok(())
+    }
+
+    /// Executes one command line, empty lines are ignored
+    pub fn execute<DB, W>(db: &mut DB, line: &str, out: &mut W) -> Result<(), W::Error>
+    where
+        DB: ShoobyDb,
+        W: Write,
+    {
+        let line = line.trim();
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.trim_start();
+
+        let mut out = FmtWriter::new(out);
+        let _ = match command {
+            "" => Ok(()),
+            "help" => out.write_str(HELP),
+            "list" => db
+                .reader()
+                .iter()
+                .try_for_each(|field| list_field(&mut out, field)),
+            "get" => match db.reader().field_by_name(args) {
+                Some(field) => field
+                    .write_value_to(&mut out)
+                    .and_then(|_| out.write_str("\r\n")),
+                None => write_error(&mut out, ShoobyError::UnknownField),
+            },
+            "set" => {
+                let (name, value) = args.split_once(' ').unwrap_or((args, ""));
+                let result = db.try_write_with(|writer| {
+                    writer
+                        .field_by_name_mut(name)
+                        .ok_or(ShoobyError::UnknownField)?
+                        .set_from_str(value)
+                });
+                write_result(&mut out, result)
+            }
+            "reset" => write_result(&mut out, db.factory_reset()),
+            _ => out.write_str("error: unknown command, try help\r\n"),
+        };
+        out.finish()
+    }
+}
+
+impl<const LINE: usize> Default for ShoobyShell<LINE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Writes a line like `SSID String = MY_HOUSE (max 32) persistent`
+fn list_field<ID: ShoobyId>(out: &mut impl FmtWrite, field: &ShoobyField<ID>) -> core::fmt::Result {
+    write!(out, "{} {:?} = ", field.short_name(), field.kind())?;
+    field.write_value_to(out)?;
+
+    match field.range() {
+        Some(ShoobyRange::Int(min, max)) => write!(out, " ({}..={})", min, max)?,
+        Some(ShoobyRange::U32(min, max)) => write!(out, " ({}..={})", min, max)?,
+        Some(ShoobyRange::I64(min, max)) => write!(out, " ({}..={})", min, max)?,
+        Some(ShoobyRange::U64(min, max)) => write!(out, " ({}..={})", min, max)?,
+        Some(ShoobyRange::Float(min, max)) => write!(out, " ({}..={})", min, max)?,
+        None if field.kind() == ShoobyKind::String => write!(out, " (max {})", field.capacity())?,
+        None => {}
+    }
+
+    match field.is_persistent() {
+        true => out.write_str(" persistent\r\n"),
+        false => out.write_str(" volatile\r\n"),
+    }
+}
+
+fn write_result(out: &mut impl FmtWrite, result: Result<(), ShoobyError>) -> core::fmt::Result {
+    match result {
+        Ok(()) => out.write_str("OK\r\n"),
+        Err(err) => write_error(out, err),
+    }
+}
+
+fn write_error(out: &mut impl FmtWrite, err: ShoobyError) -> core::fmt::Result {
+    write!(out, "error: {}\r\n", err)
+}
+
+/// core::fmt::Write over an embedded-io writer, keeps the first io error
+struct FmtWriter<'a, W: Write> {
+    out: &'a mut W,
+    error: Option<W::Error>,
+}
+
+impl<'a, W: Write> FmtWriter<'a, W> {
+    fn new(out: &'a mut W) -> Self {
+        Self { out, error: None }
+    }
+
+    fn finish(self) -> Result<(), W::Error> {
+        self.error.map_or(Ok(()), Err)
+    }
+}
+
+impl<W: Write> FmtWrite for FmtWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        if self.error.is_some() {
+            return Err(core::fmt::Error);
+        }
+        self.out.write_all(s.as_bytes()).map_err(|err| {
+            self.error = Some(err);
+            core::fmt::Error
+        })
+    }
+}
@@ -10,35 +10,87 @@ macro_rules! _shooby_static_alloc {
     ($name:ident, $t:ident, $default:expr, $range:expr) => {};
 }
 
+macro_rules! _shooby_range {
+    (Int, $range:expr) => {
+        ShoobyRange::int($range)
+    };
+
+    (U32, $range:expr) => {
+        ShoobyRange::u32($range)
+    };
+
+    (I64, $range:expr) => {
+        ShoobyRange::i64($range)
+    };
+
+    (U64, $range:expr) => {
+        ShoobyRange::u64($range)
+    };
+
+    (Float, $range:expr) => {
+        ShoobyRange::float($range)
+    };
+
+    ($var:ident, $range:expr) => {
+        None
+    };
+}
+
 macro_rules! _shooby_create_cfgs {
-    ($name:ident, String, $default:expr, $range:expr, $persistent:path) => {
+    ($name:ident, String, $default:expr, $range:expr) => {
         ShoobyField::new(
-            ID::$name,
             // safety: this happens in take function, that can only happen once
-            ShoobyFieldType::String(unsafe { &mut $name }),
-            None,
-            $persistent,
+            ShoobyFieldType::String(unsafe { &mut *core::ptr::addr_of_mut!($name) }),
+            &SCHEMA[ID::$name as usize],
         )
     };
 
-    ($name:ident, Blob, $default:expr, $range:expr, $persistent:path) => {
+    ($name:ident, Blob, $default:expr, $range:expr) => {
         ShoobyField::new(
-            ID::$name,
             // safety: this happens in take function, that can only happen once
-            ShoobyFieldType::Blob(unsafe { &mut $name as &mut [u8; $range] }),
-            None,
-            $persistent,
+            ShoobyFieldType::Blob(unsafe {
+                &mut *core::ptr::addr_of_mut!($name) as &mut [u8; $range]
+            }),
+            &SCHEMA[ID::$name as usize],
         )
     };
 
-    ($name:ident, $var:ident, $default:literal, $range:expr, $persistent:path) => {
+    ($name:ident, Enum, $default:expr, $range:expr) => {
         ShoobyField::new(
-            ID::$name,
-            ShoobyFieldType::$var($default),
-            $range,
-            $persistent,
+            ShoobyFieldType::new_enum(&$default),
+            &SCHEMA[ID::$name as usize],
         )
     };
+
+    ($name:ident, $var:ident, $default:literal, $range:expr) => {
+        ShoobyField::new(ShoobyFieldType::$var($default), &SCHEMA[ID::$name as usize])
+    };
+}
+
+/// The default of a field for FieldMeta, as a fn returning its value
+macro_rules! _shooby_default {
+    (Enum, $default:expr, $range:expr) => {
+        || enum_value(&$default)
+    };
+
+    (Blob, $default:expr, $range:expr) => {
+        || blob_value(&const { $default })
+    };
+
+    ($var:ident, $default:expr, $range:expr) => {
+        || ShoobyValue::$var($default)
+    };
+}
+
+/// The variants of an Enum field for FieldMeta
+macro_rules! _shooby_variants {
+    (Enum, $default:expr) => {
+        enum_variants(&$default)
+    };
+
+    ($var:ident, $default:expr) => {
+        &[]
+    };
 }
 
 macro_rules! _shooby_assign_value {
@@ -48,6 +100,21 @@ macro_rules! _shooby_assign_value {
     ($name:ident, Int, $value:expr, $range:expr) => {
         $name.set_num($value).unwrap();
     };
+    ($name:ident, U32, $value:expr, $range:expr) => {
+        $name.set_u32::<u32>($value).unwrap();
+    };
+    ($name:ident, I64, $value:expr, $range:expr) => {
+        $name.set_i64::<i64>($value).unwrap();
+    };
+    ($name:ident, U64, $value:expr, $range:expr) => {
+        $name.set_u64::<u64>($value).unwrap();
+    };
+    ($name:ident, Float, $value:expr, $range:expr) => {
+        $name.set_float($value).unwrap();
+    };
+    ($name:ident, Enum, $value:expr, $range:expr) => {
+        $name.set_enum($value).unwrap();
+    };
     ($name:ident, String, $value:expr, $range:expr) => {
         $name.set_string($value).unwrap();
     };
@@ -56,18 +123,298 @@ macro_rules! _shooby_assign_value {
     };
 }
 
+/// Const assertion that the default of a field fits its range or size, so a bad default is a compile error
+/// naming the field instead of a panic in take()
+macro_rules! _shooby_check_default {
+    ($name:ident, Int, $default:expr, $range:expr) => {
+        const _: () = assert!(
+            ShoobyRange::int_allows($range, $default),
+            concat!(
+                "shooby_db: the default of ",
+                stringify!($name),
+                " is out of its range"
+            )
+        );
+    };
+    ($name:ident, U32, $default:expr, $range:expr) => {
+        const _: () = assert!(
+            ShoobyRange::u32_allows($range, $default),
+            concat!(
+                "shooby_db: the default of ",
+                stringify!($name),
+                " is out of its range"
+            )
+        );
+    };
+    ($name:ident, I64, $default:expr, $range:expr) => {
+        const _: () = assert!(
+            ShoobyRange::i64_allows($range, $default),
+            concat!(
+                "shooby_db: the default of ",
+                stringify!($name),
+                " is out of its range"
+            )
+        );
+    };
+    ($name:ident, U64, $default:expr, $range:expr) => {
+        const _: () = assert!(
+            ShoobyRange::u64_allows($range, $default),
+            concat!(
+                "shooby_db: the default of ",
+                stringify!($name),
+                " is out of its range"
+            )
+        );
+    };
+    ($name:ident, Float, $default:expr, $range:expr) => {
+        const _: () = assert!(
+            ShoobyRange::float_allows($range, $default),
+            concat!(
+                "shooby_db: the default of ",
+                stringify!($name),
+                " is not finite or out of its range"
+            )
+        );
+    };
+    ($name:ident, String, $default:expr, $range:expr) => {
+        const _: () = assert!(
+            $default.len() <= $range,
+            concat!(
+                "shooby_db: the default of ",
+                stringify!($name),
+                " is longer than its size"
+            )
+        );
+    };
+    ($name:ident, Blob, $default:expr, $range:expr) => {
+        const _: () = assert!(
+            size_of_value(&$default) == $range,
+            concat!(
+                "shooby_db: the size of ",
+                stringify!($name),
+                " is not the size of its default"
+            )
+        );
+    };
+    ($name:ident, $var:ident, $default:expr, $range:expr) => {};
+}
+
+macro_rules! _shooby_data_size {
+    (Bool, $range:expr) => {
+        1
+    };
+    (Int, $range:expr) => {
+        4
+    };
+    (U32, $range:expr) => {
+        4
+    };
+    (I64, $range:expr) => {
+        8
+    };
+    (U64, $range:expr) => {
+        8
+    };
+    (Float, $range:expr) => {
+        4
+    };
+    (Enum, $range:expr) => {
+        4
+    };
+    (String, $range:expr) => {
+        $range
+    };
+    (Blob, $range:expr) => {
+        $range
+    };
+}
+
+/// A typed getter of a field, for the Reader trait generated by shooby_db!.
+/// Enum and Blob fields need their type in shooby_db! to get a typed getter, else the getter is generic
+macro_rules! _shooby_getter {
+    ($name:ident, Bool, []) => {
+        $crate::paste::paste! {
+            fn [<$name:lower>](&self) -> bool {
+                self.as_fields()[ID::$name].get_bool().unwrap()
+            }
+        }
+    };
+    ($name:ident, Int, []) => {
+        $crate::paste::paste! {
+            fn [<$name:lower>](&self) -> i32 {
+                self.as_fields()[ID::$name].get_int::<i32>().unwrap()
+            }
+        }
+    };
+    ($name:ident, U32, []) => {
+        $crate::paste::paste! {
+            fn [<$name:lower>](&self) -> u32 {
+                self.as_fields()[ID::$name].get_u32::<u32>().unwrap()
+            }
+        }
+    };
+    ($name:ident, I64, []) => {
+        $crate::paste::paste! {
+            fn [<$name:lower>](&self) -> i64 {
+                self.as_fields()[ID::$name].get_i64::<i64>().unwrap()
+            }
+        }
+    };
+    ($name:ident, U64, []) => {
+        $crate::paste::paste! {
+            fn [<$name:lower>](&self) -> u64 {
+                self.as_fields()[ID::$name].get_u64::<u64>().unwrap()
+            }
+        }
+    };
+    ($name:ident, Float, []) => {
+        $crate::paste::paste! {
+            fn [<$name:lower>](&self) -> f32 {
+                self.as_fields()[ID::$name].get_float::<f32>().unwrap()
+            }
+        }
+    };
+    ($name:ident, String, []) => {
+        $crate::paste::paste! {
+            fn [<$name:lower>](&self) -> &str {
+                self.as_fields()[ID::$name].get_string().unwrap_or_default()
+            }
+        }
+    };
+    ($name:ident, Enum, [$ty:ty]) => {
+        $crate::paste::paste! {
+            fn [<$name:lower>](&self) -> $ty {
+                self.as_fields()[ID::$name].get_enum::<$ty>().unwrap()
+            }
+        }
+    };
+    ($name:ident, Enum, []) => {
+        $crate::paste::paste! {
+            fn [<$name:lower>]<E: ShoobyEnum>(&self) -> Result<E, ShoobyError> {
+                self.as_fields()[ID::$name].get_enum::<E>()
+            }
+        }
+    };
+    ($name:ident, Blob, [$ty:ty]) => {
+        $crate::paste::paste! {
+            fn [<$name:lower>](&self) -> &$ty {
+                self.as_fields()[ID::$name].get_blob::<$ty>().unwrap()
+            }
+        }
+    };
+    ($name:ident, Blob, []) => {
+        $crate::paste::paste! {
+            fn [<$name:lower>]<T: Sized>(&self) -> Result<&T, ShoobyError> {
+                self.as_fields()[ID::$name].get_blob::<T>()
+            }
+        }
+    };
+}
+
+/// A typed setter of a field, for the Writer trait generated by shooby_db!
+macro_rules! _shooby_setter {
+    ($name:ident, Bool, []) => {
+        $crate::paste::paste! {
+            fn [<set_ $name:lower>](&mut self, value: bool) -> Result<bool, ShoobyError> {
+                self.field_mut(ID::$name).set_bool(value)
+            }
+        }
+    };
+    ($name:ident, Int, []) => {
+        $crate::paste::paste! {
+            fn [<set_ $name:lower>]<T: Into<i32>>(&mut self, value: T) -> Result<i32, ShoobyError> {
+                self.field_mut(ID::$name).set_num(value.into())
+            }
+        }
+    };
+    ($name:ident, U32, []) => {
+        $crate::paste::paste! {
+            fn [<set_ $name:lower>]<T: Into<u32>>(&mut self, value: T) -> Result<u32, ShoobyError> {
+                self.field_mut(ID::$name).set_u32(value.into())
+            }
+        }
+    };
+    ($name:ident, I64, []) => {
+        $crate::paste::paste! {
+            fn [<set_ $name:lower>]<T: Into<i64>>(&mut self, value: T) -> Result<i64, ShoobyError> {
+                self.field_mut(ID::$name).set_i64(value.into())
+            }
+        }
+    };
+    ($name:ident, U64, []) => {
+        $crate::paste::paste! {
+            fn [<set_ $name:lower>]<T: Into<u64>>(&mut self, value: T) -> Result<u64, ShoobyError> {
+                self.field_mut(ID::$name).set_u64(value.into())
+            }
+        }
+    };
+    ($name:ident, Float, []) => {
+        $crate::paste::paste! {
+            fn [<set_ $name:lower>]<T: Into<f32>>(&mut self, value: T) -> Result<f32, ShoobyError> {
+                self.field_mut(ID::$name).set_float(value.into())
+            }
+        }
+    };
+    ($name:ident, String, []) => {
+        $crate::paste::paste! {
+            fn [<set_ $name:lower>](&mut self, value: &str) -> Result<(), ShoobyError> {
+                self.field_mut(ID::$name).set_string(value)
+            }
+        }
+    };
+    ($name:ident, Enum, [$ty:ty]) => {
+        $crate::paste::paste! {
+            fn [<set_ $name:lower>](&mut self, value: $ty) -> Result<$ty, ShoobyError> {
+                self.field_mut(ID::$name).set_enum(value)
+            }
+        }
+    };
+    ($name:ident, Enum, []) => {
+        $crate::paste::paste! {
+            fn [<set_ $name:lower>]<E: ShoobyEnum>(&mut self, value: E) -> Result<E, ShoobyError> {
+                self.field_mut(ID::$name).set_enum(value)
+            }
+        }
+    };
+    ($name:ident, Blob, [$ty:ty]) => {
+        $crate::paste::paste! {
+            fn [<set_ $name:lower>](&mut self, value: &$ty) -> Result<(), ShoobyError> {
+                self.field_mut(ID::$name).set_blob(value)
+            }
+        }
+    };
+    ($name:ident, Blob, []) => {
+        $crate::paste::paste! {
+            fn [<set_ $name:lower>]<T: Sized>(&mut self, value: &T) -> Result<(), ShoobyError> {
+                self.field_mut(ID::$name).set_blob(value)
+            }
+        }
+    };
+}
+
 /// This is the main macro that creates the Database and the fields defined by the user
+/// An optional schema version can be given after the DB name, it defaults to 0:
+///     `shooby_db!(NAME, version = 2 => {...},);`
+/// When the version stored with the persisted data differs, DB::init_with_migration will call the migration
+/// Enum and Blob fields can name their type to get typed accessors in the Reader and Writer traits:
+///     `{LOG_LEVEL, Enum(Level), Level::Info, None, PERSISTENT},`
+/// The type of a Blob must have no padding bytes, like a `#[repr(C, packed)]` struct, its bytes are
+/// read as they are to compare, save and export the value
 #[macro_export]
 macro_rules! shooby_db {
-    ($DB_NAME:ident => $({$name:ident, $var:ident, $default:expr, $range:expr, $persistent:path},)+ ) => {
+    ($DB_NAME:ident => $({$name:ident, $var:ident $(($ty:ty))?, $default:expr, $range:expr, $persistent:path},)+ ) => {
+        $crate::shooby_db!($DB_NAME, version = 0 => $({$name, $var $(($ty))?, $default, $range, $persistent},)+);
+    };
+
+    ($DB_NAME:ident, version = $version:expr => $({$name:ident, $var:ident $(($ty:ty))?, $default:expr, $range:expr, $persistent:path},)+ ) => {
 
-        #[allow(non_camel_case_types, non_snake_case)]
+        #[allow(non_camel_case_types, non_snake_case, dead_code, clippy::upper_case_acronyms)]
         mod $DB_NAME  {
 
             use super::*;
-            use std::sync::atomic::AtomicBool;
-            use std::sync::atomic::Ordering;
-            use std::fmt::{Formatter, Display, Result as FmtResult};
+            use core::sync::atomic::AtomicBool;
+            use core::sync::atomic::Ordering;
+            use core::fmt::{Formatter, Display, Result as FmtResult};
 
             // =============== CONFIGURATION ID ====================================
             // This is the ID of the configuration to index the db by
@@ -77,10 +424,12 @@ macro_rules! shooby_db {
                 FIELD_NUM
             }
 
-            static _ID_AS_STR: [&str; ID::FIELD_NUM as usize] = [
+            // FIELD_NUM is the key of the schema version in the storage, so it has a name as well
+            static _ID_AS_STR: [&str; ID::FIELD_NUM as usize + 1] = [
                 $(
                     concat!(stringify!($DB_NAME), "::ID::", stringify!($name)),
                 )*
+                concat!(stringify!($DB_NAME), "::ID::FIELD_NUM"),
             ];
 
             impl AsRef<str> for ID {
@@ -99,7 +448,76 @@ macro_rules! shooby_db {
                 }
             }
 
-            impl std::ops::Index <ID> for [ShoobyField<ID>] {
+            const ID_SET_WORDS: usize = (ID::FIELD_NUM as usize).div_ceil(32);
+
+            /// A set of IDs of this DB, to choose the fields an observer is interested in
+            pub type IdSet = ShoobyIdSet<ID, ID_SET_WORDS>;
+
+            /// Builds an IdSet at compile time
+            ///     `const NETWORK: IdSet = id_set(&[ID::SSID, ID::PASSWORD]);`
+            pub const fn id_set(ids: &[ID]) -> IdSet {
+                let mut set = IdSet::empty();
+                let mut i = 0;
+                while i < ids.len() {
+                    set = set.with_index(ids[i] as usize);
+                    i += 1;
+                }
+                set
+            }
+
+            static _ALL_IDS: [ID; ID::FIELD_NUM as usize] = [$(ID::$name,)*];
+
+            /// The declaration of every field, by ID order
+            pub static SCHEMA: [FieldMeta<ID>; ID::FIELD_NUM as usize] = [
+                $(
+                    FieldMeta::new(
+                        ID::$name,
+                        ShoobyKind::$var,
+                        _shooby_default!($var, $default, $range),
+                        _shooby_range!($var, $range),
+                        _shooby_data_size!($var, $range),
+                        $persistent,
+                        _shooby_variants!($var, $default),
+                    ),
+                )*
+            ];
+
+            // a default that does not fit its field fails the build here
+            $( _shooby_check_default!($name, $var, $default, $range); )*
+
+            impl ShoobyId for ID {
+                const FIELD_COUNT: usize = ID::FIELD_NUM as usize;
+                const SCHEMA_VERSION_KEY: Self = ID::FIELD_NUM;
+
+                fn index(&self) -> usize {
+                    *self as usize
+                }
+
+                fn from_index(index: usize) -> Option<Self> {
+                    _ALL_IDS.get(index).copied()
+                }
+
+                fn from_name(name: &str) -> Option<Self> {
+                    let short = name
+                        .strip_prefix(concat!(stringify!($DB_NAME), "::ID::"))
+                        .unwrap_or(name);
+                    match short {
+                        $(stringify!($name) => Some(ID::$name),)*
+                        _ => None,
+                    }
+                }
+            }
+
+            impl core::str::FromStr for ID {
+                type Err = ShoobyError;
+
+                /// Parses the short (`SSID`) or qualified (`NAME::ID::SSID`) name of a field
+                fn from_str(name: &str) -> Result<Self, Self::Err> {
+                    <ID as ShoobyId>::from_name(name).ok_or(ShoobyError::UnknownField)
+                }
+            }
+
+            impl core::ops::Index <ID> for [ShoobyField<ID>] {
                 type Output = ShoobyField<ID>;
 
                 fn index(&self, index: ID) -> &Self::Output {
@@ -107,14 +525,68 @@ macro_rules! shooby_db {
                 }
             }
 
-            impl std::ops::IndexMut <ID> for [ShoobyField<ID>] {
+            impl core::ops::IndexMut <ID> for [ShoobyField<ID>] {
                 fn index_mut(&mut self, index: ID) -> &mut Self::Output {
                     &mut  self[index as usize ]
                 }
             }
 
+            impl core::ops::Index <ID> for [LoadStatus] {
+                type Output = LoadStatus;
+
+                fn index(&self, index: ID) -> &Self::Output {
+                    & self[index as usize]
+                }
+            }
+
             // ================= CONFIGURATION ID END =================
 
+            // ================= SCHEMA =================
+
+            /// The schema version given to shooby_db!, saved to the storage on init
+            pub const SCHEMA_VERSION: u32 = $version;
+
+            /// The size of the biggest persisted field record, used as a scratch buffer for the storage
+            const RECORD_SIZE: usize = record_size(&[$(_shooby_data_size!($var, $range)),*]);
+            /// The stack taken by write_with and try_write_with to stage the previous values of the changed
+            /// fields, to roll them back and notify the observer. It fits the values of all the fields
+            pub const BACKUP_SIZE: usize = 0 $(+ _shooby_data_size!($var, $range))*;
+
+            /// The size of a snapshot of the DB, a buffer of this size fits DB::export_to
+            pub const SNAPSHOT_SIZE: usize = $crate::snapshot::snapshot_size(stringify!($DB_NAME), &[$(_shooby_data_size!($var, $range)),*]);
+
+            // ================= TYPED ACCESSORS =================
+
+            /// Typed getters for the fields, named after the fields in lower case.
+            /// Implemented for the reader, bring it in scope to use: `use NAME::Reader;`
+            ///     `let ssid: &str = db.reader().ssid();`
+            pub trait Reader {
+                fn as_fields(&self) -> &[ShoobyField<ID>];
+
+                $( _shooby_getter!($name, $var, [$($ty)?]); )*
+            }
+
+            impl Reader for [ShoobyField<ID>] {
+                fn as_fields(&self) -> &[ShoobyField<ID>] {
+                    self
+                }
+            }
+
+            /// Typed setters for the fields, named set_ and the field name in lower case.
+            /// Implemented for the writer, bring it in scope to use: `use NAME::Writer;`
+            ///     `db.write_with(|writer| { writer.set_connection_retries(5u8).unwrap(); });`
+            pub trait Writer {
+                fn field_mut(&mut self, id: ID) -> FieldWriter<'_, ID>;
+
+                $( _shooby_setter!($name, $var, [$($ty)?]); )*
+            }
+
+            impl Writer for ShoobyWriter<'_, ID> {
+                fn field_mut(&mut self, id: ID) -> FieldWriter<'_, ID> {
+                    ShoobyWriter::field_mut(self, id)
+                }
+            }
+
             // ================= EMPTY STRUCT AS DEFAULTS =================
 
             pub struct EmptyObserver;
@@ -124,6 +596,15 @@ macro_rules! shooby_db {
                 fn update(&self, _field: &ShoobyField<Self::ID>) {}
             }
 
+            pub struct EmptyInterceptor;
+            impl ShoobyInterceptor for EmptyInterceptor {
+                type ID = ID;
+
+                fn before_write(&self, _field: &ShoobyField<Self::ID>, _new: &ShoobyValue<'_>) -> Result<(), ShoobyError> {
+                    Ok(())
+                }
+            }
+
             pub struct EmptyStorage;
             impl ShoobyStorage for EmptyStorage {
                 type ID = ID;
@@ -153,10 +634,15 @@ macro_rules! shooby_db {
 
             /// This is the main struct that holds the database
             /// A new struct will be generated for call to macro shooby_db!
-            pub struct DB<Observer: ShoobyObserver<ID=ID> = EmptyObserver, Storage: ShoobyStorage<ID=ID> = EmptyStorage> {
+            pub struct DB<
+                Observer: ShoobyObserver<ID=ID> = EmptyObserver,
+                Storage: ShoobyStorage<ID=ID> = EmptyStorage,
+                Interceptor: ShoobyInterceptor<ID=ID> = EmptyInterceptor,
+            > {
                 items: &'static mut [ShoobyField<ID>],
                 observer: Option<Observer>,
                 storage: Option<Storage>,
+                interceptor: Interceptor,
                 // RWLock for the array / wrapper of the array
             }
 
@@ -177,7 +663,6 @@ macro_rules! shooby_db {
                 pub fn take(observer: Option<Observer>, storage: Option<Storage>) -> Self {
 
                     // make sure we call this function only one time!
-                    // TODO: limit the AtomicBool to only if feature std is enabled
                     static TAKEN: AtomicBool = AtomicBool::new(false);
                     let taken = TAKEN.fetch_or(true, Ordering::Relaxed);
                     if taken {
@@ -189,7 +674,7 @@ macro_rules! shooby_db {
 
                     // creates the array of fields
                     static mut ITEMS: &'static mut [ShoobyField<ID>] = &mut [
-                        $(_shooby_create_cfgs!($name, $var, $default, $range, $persistent), ) *
+                        $(_shooby_create_cfgs!($name, $var, $default, $range), ) *
                     ];
 
                     // creates the DB struct with all data supplied
@@ -197,6 +682,7 @@ macro_rules! shooby_db {
                         items: unsafe { ITEMS },
                         observer,
                         storage,
+                        interceptor: EmptyInterceptor,
                     };
 
                     // reset all fields to default
@@ -208,11 +694,81 @@ macro_rules! shooby_db {
                     s
                 }
 
+                /// Gives the DB an interceptor that is asked before every field change in write_with and
+                /// try_write_with. The DB owns it, so it can hold state like the region of the device
+                ///     `let db = NAME::take_db_with_empty_observer_and_storage().with_interceptor(Policy::new(region));`
+                pub fn with_interceptor<Interceptor: ShoobyInterceptor<ID=ID>>(self, interceptor: Interceptor) -> DB<Observer, Storage, Interceptor> {
+                    DB {
+                        items: self.items,
+                        observer: self.observer,
+                        storage: self.storage,
+                        interceptor,
+                    }
+                }
+            }
+
+            impl<Observer: ShoobyObserver<ID=ID>, Storage: ShoobyStorage<ID=ID>, Interceptor: ShoobyInterceptor<ID=ID>> DB<Observer, Storage, Interceptor> {
+
+                /// Loads all persistent fields from the storage. Should be called once at boot, right after take.
+                /// Fields that are not persistent, were never saved or hold a corrupted value keep their default.
+                /// Fields saved with a different type or size are reset to default and saved again.
+                /// The observer is NOT notified on loaded values
+                /// returns: the source of every field value, indexable by ID
+                ///     `let report = db.init()?; if report[NAME::ID::SSID] == LoadStatus::Loaded {...}`
+                pub fn init(&mut self) -> Result<[LoadStatus; ID::FIELD_NUM as usize], ShoobyError> {
+                    self.init_with_migration(|_, _, _| {})
+                }
+
+                /// Same as init, but if the schema version saved in the storage differs from SCHEMA_VERSION
+                /// the migration is called after all fields were loaded, with the saved version, the fields
+                /// and the storage to read data in older formats from.
+                /// Fields changed by the migration are saved, then SCHEMA_VERSION is saved to the storage.
+                pub fn init_with_migration<F>(&mut self, migrate: F) -> Result<[LoadStatus; ID::FIELD_NUM as usize], ShoobyError>
+                where F: FnOnce(u32, &mut [ShoobyField<ID>], &mut Storage) {
+                    self.reset_changed_flags();
+
+                    let stored_version = match self.storage.as_mut() {
+                        Some(storage) => storage.load_schema_version()?,
+                        None => None,
+                    };
+
+                    let report = self.load_from_storage();
+
+                    // rewrite fields that were saved with an old layout
+                    for (item, status) in self.items.iter_mut().zip(report.iter()) {
+                        if *status == LoadStatus::LayoutChanged {
+                            item.has_changed = true;
+                        }
+                    }
+
+                    if let Some(storage) = self.storage.as_mut() {
+                        match stored_version {
+                            Some(version) if version != SCHEMA_VERSION => migrate(version, self.items, storage),
+                            _ => {}
+                        }
+                    }
+
+                    let saved = self.save_to_storage();
+                    self.reset_changed_flags();
+                    saved?;
+
+                    if let Some(storage) = self.storage.as_ref() {
+                        if stored_version != Some(SCHEMA_VERSION) {
+                            storage.save_schema_version(SCHEMA_VERSION)?;
+                        }
+                    }
+
+                    Ok(report)
+                }
+
                 /// This function reset all values to default and saves them to persistent storage if needed
                 /// The function will NOT notify observer on changes
                 pub fn factory_reset(&mut self) -> Result<(), ShoobyError> {
                     self.reset_to_default();
-                    self.save_to_storage()
+                    let saved = self.save_to_storage();
+                    // the next write must not see the reset fields as changed
+                    self.reset_changed_flags();
+                    saved
                 }
 
                 /// Get the DB name as string reference
@@ -225,11 +781,73 @@ macro_rules! shooby_db {
                     self.items
                 }
 
-                /// Get the DB array of fields to write to inside a closure
-                pub fn write_with<F>(&mut self, f: F) where F: FnOnce(&mut [ShoobyField<ID>]) {
-                    f(self.items);
-                    self.save_to_storage();
-                    self.update_observer();
+                /// Get a field to read from by its short (`SSID`) or qualified (`NAME::ID::SSID`) name.
+                /// To write by name use field_by_name_mut of the writer inside write_with
+                pub fn field_by_name(&self, name: &str) -> Option<&ShoobyField<ID>> {
+                    self.items.field_by_name(name)
+                }
+
+                /// Writes a snapshot of all fields to the start of out, to back up the DB or move it to
+                /// another device. returns the snapshot size, InvalidSize if out is smaller than SNAPSHOT_SIZE
+                ///     `let mut buffer = [0u8; NAME::SNAPSHOT_SIZE]; let len = db.export_to(&mut buffer)?;`
+                pub fn export_to(&self, out: &mut [u8]) -> Result<usize, ShoobyError> {
+                    $crate::snapshot::export_snapshot(stringify!($DB_NAME), self.items, out)
+                }
+
+                /// Sets all fields from a snapshot made by export_to, in one write through the validating setters.
+                /// If any field is rejected nothing is changed and its error is returned.
+                /// A snapshot of another DB or of a DB with different fields returns LayoutChanged,
+                /// a damaged one CorruptedRecord and one that can't be parsed InvalidFormat
+                ///     `db.import_from(&buffer[..len])?;`
+                pub fn import_from(&mut self, snapshot: &[u8]) -> Result<(), ShoobyError> {
+                    self.try_write_with(|writer| $crate::snapshot::import_snapshot(stringify!($DB_NAME), writer, snapshot))
+                }
+
+                /// Get the DB fields to write to inside a closure, see ShoobyWriter
+                ///     `db.write_with(|writer| { writer.field_mut(NAME::ID::SSID).set_string("home").unwrap(); });`
+                /// The previous values of the changed fields are staged on the stack, see BACKUP_SIZE
+                pub fn write_with<F>(&mut self, f: F) where F: FnOnce(&mut ShoobyWriter<'_, ID>) {
+                    // the observer gets the previous values from the staging buffer
+                    let mut staging = [0u8; BACKUP_SIZE];
+                    // a panic in f drops the writer, which rolls back what f changed
+                    let mut writer = ShoobyWriter::new(self.items, &mut staging, &self.interceptor);
+                    f(&mut writer);
+                    writer.finish();
+                    let _ = self.save_to_storage();
+                    self.update_observer(&staging);
+                }
+
+                /// Same as write_with, but the closure can fail.
+                /// The changes are saved and the observer is notified only if the closure returns Ok.
+                /// If the closure or the storage fails, all changed fields get back their previous values
+                /// and the error is returned
+                ///     `db.try_write_with(|writer| { writer.field_mut(NAME::ID::A).set_num(1)?; writer.field_mut(NAME::ID::B).set_num(2) })?;`
+                pub fn try_write_with<T, E, F>(&mut self, f: F) -> Result<T, E>
+                where F: FnOnce(&mut ShoobyWriter<'_, ID>) -> Result<T, E>, E: From<ShoobyError> {
+                    let mut staging = [0u8; BACKUP_SIZE];
+                    let mut writer = ShoobyWriter::new(self.items, &mut staging, &self.interceptor);
+                    let result = f(&mut writer);
+                    writer.finish();
+
+                    let value = match result {
+                        Ok(value) => value,
+                        Err(err) => {
+                            self.restore_staged(&staging);
+                            self.reset_changed_flags();
+                            return Err(err);
+                        }
+                    };
+
+                    if let Err(err) = self.save_to_storage() {
+                        // some fields may be saved already, try to put back the previous values there too
+                        self.restore_staged(&staging);
+                        let _ = self.save_to_storage();
+                        self.reset_changed_flags();
+                        return Err(err.into());
+                    }
+
+                    self.update_observer(&staging);
+                    Ok(value)
                 }
 
                 /// Perform an operation on the observer object if it exists
@@ -237,6 +855,11 @@ macro_rules! shooby_db {
                     f(self.observer.as_mut());
                 }
 
+                /// Perform an operation on the interceptor given by with_interceptor, like changing its policy
+                pub fn interceptor<F>(&mut self, f: F) where F: FnOnce(&mut Interceptor) {
+                    f(&mut self.interceptor);
+                }
+
                 //============PRIVATE FUNCTIONS================
 
                 pub fn reset_to_default(&mut self) {
@@ -247,34 +870,84 @@ macro_rules! shooby_db {
                 }
 
 
-                fn update_observer(&mut self) {
+                /// Notifies the observer on the changed fields, their previous values are in staging
+                fn update_observer(&mut self, staging: &[u8]) {
                     if let Some(observer) = self.observer.as_ref() {
-                        for item in self.items.as_mut() {
-                            if (item.has_changed) {
-                                observer.update(item);
-                                item.has_changed = false;
+                        let mut offset = 0;
+                        for item in self.items.as_ref() {
+                            if (item.has_changed && observer.is_interested(&item.id())) {
+                                observer.on_change(&ShoobyChange {
+                                    id: item.id(),
+                                    old: item.backup_value(&staging[offset..]),
+                                    new: item.value(),
+                                    field: item,
+                                });
                             }
+                            offset += item.data_len();
                         }
                     }
+
+                    self.reset_changed_flags();
+                }
+
+                /// Restores the changed fields from staging
+                fn restore_staged(&mut self, staging: &[u8]) {
+                    $crate::writer::restore_staged(self.items, staging);
+                }
+
+                fn reset_field_to_default(&mut self, id: ID) {
+                    match id {
+                        $(
+                            ID::$name => {
+                                let data = &mut self.items[ID::$name];
+                                _shooby_assign_value!(data, $var, $default, $range);
+                            }
+                        )*
+                        ID::FIELD_NUM => {}
+                    }
                 }
 
-                fn load_from_storage(&mut self) -> Result<bool, ShoobyError> {
-                    let mut loaded = false;
+                fn load_from_storage(&mut self) -> [LoadStatus; ID::FIELD_NUM as usize] {
+                    let mut report = [LoadStatus::Default; ID::FIELD_NUM as usize];
 
                     if let Some(storage) = self.storage.as_mut() {
-                        for item in self.items.as_mut() {
-                            loaded &= item.load(storage)?;
+                        let mut scratch = [0u8; RECORD_SIZE];
+                        for (item, status) in self.items.iter_mut().zip(report.iter_mut()) {
+                            *status = match item.load(storage, &mut scratch) {
+                                Ok(true) => LoadStatus::Loaded,
+                                Ok(false) => LoadStatus::Default,
+                                Err(ShoobyError::LayoutChanged) => LoadStatus::LayoutChanged,
+                                Err(_) => LoadStatus::Corrupted,
+                            };
                         }
                     }
 
-                    Ok(loaded)
+                    // fields that failed to load get their default even if they were changed before init
+                    for index in 0..self.items.len() {
+                        if matches!(report[index], LoadStatus::Corrupted | LoadStatus::LayoutChanged) {
+                            let id = self.items[index].id();
+                            self.reset_field_to_default(id);
+                        }
+                    }
+
+                    report
                 }
 
+                /// Saves the changed fields in one batch of the storage
                 fn save_to_storage(&self) -> Result<(), ShoobyError> {
                     if let Some(storage) = self.storage.as_ref() {
-                        for item in self.items.as_ref() {
-                            if (item.has_changed) {
-                                item.save(storage)?;
+                        let mut scratch = [0u8; RECORD_SIZE];
+                        storage.begin_batch();
+                        let saved = self.items
+                            .iter()
+                            .filter(|item| item.has_changed)
+                            .try_for_each(|item| item.save(storage, &mut scratch));
+
+                        match saved {
+                            Ok(()) => storage.commit_batch()?,
+                            Err(err) => {
+                                storage.abort_batch();
+                                return Err(err);
                             }
                         }
                     }
@@ -289,6 +962,27 @@ macro_rules! shooby_db {
                 }
 
             }
+
+            impl<Observer: ShoobyObserver<ID=ID>, Storage: ShoobyStorage<ID=ID>, Interceptor: ShoobyInterceptor<ID=ID>> ShoobyDb for DB<Observer, Storage, Interceptor> {
+                type ID = ID;
+
+                fn reader(&self) -> &[ShoobyField<ID>] {
+                    DB::reader(self)
+                }
+
+                fn write_with<F>(&mut self, f: F) where F: FnOnce(&mut ShoobyWriter<'_, ID>) {
+                    DB::write_with(self, f)
+                }
+
+                fn try_write_with<T, E, F>(&mut self, f: F) -> Result<T, E>
+                where F: FnOnce(&mut ShoobyWriter<'_, ID>) -> Result<T, E>, E: From<ShoobyError> {
+                    DB::try_write_with(self, f)
+                }
+
+                fn factory_reset(&mut self) -> Result<(), ShoobyError> {
+                    DB::factory_reset(self)
+                }
+            }
         }
             // ================= CONFIGURATION DB END =================
         };
@@ -1,23 +1,73 @@
 #![allow(dead_code)]
 
 use crate::utils::*;
-use crate::{errors::ShoobyError, ShoobyStorage};
-use std::fmt::{Display, Formatter, Result as FmtResult};
-use std::mem::size_of;
+use crate::{errors::ShoobyError, ShoobyEnum, ShoobyStorage};
+use core::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use core::mem::size_of;
 
 #[derive(Debug)]
 pub(crate) enum ShoobyFieldType {
     Bool(bool),
     Int(i32),
+    U32(u32),
+    I64(i64),
+    U64(u64),
+    Float(f32),
+    Enum(u32, &'static [(u32, &'static str)]),
     String(&'static mut [u8]),
     Blob(&'static mut [u8]),
 }
 
+impl ShoobyFieldType {
+    /// A unique tag for every field type
+    pub(crate) fn type_tag(&self) -> u8 {
+        match self {
+            ShoobyFieldType::Bool(_) => 1,
+            ShoobyFieldType::Int(_) => 2,
+            ShoobyFieldType::U32(_) => 3,
+            ShoobyFieldType::I64(_) => 4,
+            ShoobyFieldType::U64(_) => 5,
+            ShoobyFieldType::Float(_) => 6,
+            ShoobyFieldType::Enum(_, _) => 7,
+            ShoobyFieldType::String(_) => 8,
+            ShoobyFieldType::Blob(_) => 9,
+        }
+    }
+
+    /// The size of the value when persisted
+    pub(crate) fn data_len(&self) -> usize {
+        match self {
+            ShoobyFieldType::Bool(_) => size_of::<u8>(),
+            ShoobyFieldType::Int(_) => size_of::<i32>(),
+            ShoobyFieldType::U32(_) => size_of::<u32>(),
+            ShoobyFieldType::I64(_) => size_of::<i64>(),
+            ShoobyFieldType::U64(_) => size_of::<u64>(),
+            ShoobyFieldType::Float(_) => size_of::<f32>(),
+            ShoobyFieldType::Enum(_, _) => size_of::<u32>(),
+            ShoobyFieldType::String(data) | ShoobyFieldType::Blob(data) => data.len(),
+        }
+    }
+
+    /// Used by the shooby_db! macro to create an Enum field from its default value.
+    /// The field holds the first variant until the default is assigned in take function
+    pub(crate) const fn new_enum<E: ShoobyEnum>(_default: &E) -> Self {
+        ShoobyFieldType::Enum(E::VARIANTS[0].0, E::VARIANTS)
+    }
+}
+
 impl Display for ShoobyFieldType {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         match self {
             ShoobyFieldType::Bool(data) => write!(f, "Bool({})", data),
             ShoobyFieldType::Int(data) => write!(f, "Int({})", data),
+            ShoobyFieldType::U32(data) => write!(f, "U32({})", data),
+            ShoobyFieldType::I64(data) => write!(f, "I64({})", data),
+            ShoobyFieldType::U64(data) => write!(f, "U64({})", data),
+            ShoobyFieldType::Float(data) => write!(f, "Float({})", data),
+            ShoobyFieldType::Enum(data, variants) => match variant_name(variants, *data) {
+                Some(name) => write!(f, "Enum({})", name),
+                None => write!(f, "Enum error(unknown discriminant {})", data),
+            },
             ShoobyFieldType::Blob(data) => write!(f, "Blob of size: {})", data.len()),
             ShoobyFieldType::String(data) => match str_from_u8_nul_utf8(data) {
                 Ok(data) => write!(f, "String({})", data),
@@ -27,32 +77,447 @@ impl Display for ShoobyFieldType {
     }
 }
 
+/// A typed view of a field value, strings and blobs borrow the field data
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShoobyValue<'a> {
+    Bool(bool),
+    Int(i32),
+    U32(u32),
+    I64(i64),
+    U64(u64),
+    Float(f32),
+    /// The discriminant and the name of the variant
+    Enum(u32, &'static str),
+    String(&'a str),
+    Blob(&'a [u8]),
+}
+
+impl Display for ShoobyValue<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            ShoobyValue::Bool(val) => write!(f, "{}", val),
+            ShoobyValue::Int(val) => write!(f, "{}", val),
+            ShoobyValue::U32(val) => write!(f, "{}", val),
+            ShoobyValue::I64(val) => write!(f, "{}", val),
+            ShoobyValue::U64(val) => write!(f, "{}", val),
+            ShoobyValue::Float(val) => write!(f, "{}", val),
+            ShoobyValue::Enum(_, name) => write!(f, "{}", name),
+            ShoobyValue::String(val) => write!(f, "{}", val),
+            ShoobyValue::Blob(data) => {
+                for byte in data.iter() {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A change of a field value, given to the observer after a write
+#[derive(Debug)]
+pub struct ShoobyChange<'a, ID: 'static> {
+    pub id: ID,
+    /// The value before the write
+    pub old: ShoobyValue<'a>,
+    /// The value after the write, same as field.value()
+    pub new: ShoobyValue<'a>,
+    /// The field after the write
+    pub field: &'a ShoobyField<ID>,
+}
+
+fn variant_name(variants: &[(u32, &'static str)], discriminant: u32) -> Option<&'static str> {
+    variants
+        .iter()
+        .find(|(value, _)| *value == discriminant)
+        .map(|(_, name)| *name)
+}
+
+/// The allowed limits of a numeric field, minimum and maximum are inclusive
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShoobyRange {
+    Int(i32, i32),
+    U32(u32, u32),
+    I64(i64, i64),
+    U64(u64, u64),
+    Float(f32, f32),
+}
+
+impl ShoobyRange {
+    pub(crate) const fn int(range: Option<(i32, i32)>) -> Option<Self> {
+        match range {
+            Some((min, max)) => Some(ShoobyRange::Int(min, max)),
+            None => None,
+        }
+    }
+
+    pub(crate) const fn u32(range: Option<(u32, u32)>) -> Option<Self> {
+        match range {
+            Some((min, max)) => Some(ShoobyRange::U32(min, max)),
+            None => None,
+        }
+    }
+
+    pub(crate) const fn i64(range: Option<(i64, i64)>) -> Option<Self> {
+        match range {
+            Some((min, max)) => Some(ShoobyRange::I64(min, max)),
+            None => None,
+        }
+    }
+
+    pub(crate) const fn u64(range: Option<(u64, u64)>) -> Option<Self> {
+        match range {
+            Some((min, max)) => Some(ShoobyRange::U64(min, max)),
+            None => None,
+        }
+    }
+
+    pub(crate) const fn float(range: Option<(f32, f32)>) -> Option<Self> {
+        match range {
+            Some((min, max)) => Some(ShoobyRange::Float(min, max)),
+            None => None,
+        }
+    }
+
+    // used by shooby_db! to check the defaults at compile time, no range allows any value
+
+    pub(crate) const fn int_allows(range: Option<(i32, i32)>, value: i32) -> bool {
+        match range {
+            Some((min, max)) => min <= value && value <= max,
+            None => true,
+        }
+    }
+
+    pub(crate) const fn u32_allows(range: Option<(u32, u32)>, value: u32) -> bool {
+        match range {
+            Some((min, max)) => min <= value && value <= max,
+            None => true,
+        }
+    }
+
+    pub(crate) const fn i64_allows(range: Option<(i64, i64)>, value: i64) -> bool {
+        match range {
+            Some((min, max)) => min <= value && value <= max,
+            None => true,
+        }
+    }
+
+    pub(crate) const fn u64_allows(range: Option<(u64, u64)>, value: u64) -> bool {
+        match range {
+            Some((min, max)) => min <= value && value <= max,
+            None => true,
+        }
+    }
+
+    /// A float default must also be finite, set_float rejects NaN even without a range
+    pub(crate) const fn float_allows(range: Option<(f32, f32)>, value: f32) -> bool {
+        match range {
+            Some((min, max)) => value.is_finite() && min <= value && value <= max,
+            None => value.is_finite(),
+        }
+    }
+}
+
+/// Maps a numeric type to its limits in ShoobyRange
+trait RangeLimits: Copy + PartialOrd {
+    fn limits(range: Option<ShoobyRange>) -> Option<(Self, Self)>;
+}
+
+macro_rules! impl_range_limits {
+    ($($t:ty => $variant:ident),*) => {
+        $(
+            impl RangeLimits for $t {
+                fn limits(range: Option<ShoobyRange>) -> Option<(Self, Self)> {
+                    match range {
+                        Some(ShoobyRange::$variant(min, max)) => Some((min, max)),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_range_limits!(i32 => Int, u32 => U32, i64 => I64, u64 => U64, f32 => Float);
+
+fn check_range<T: RangeLimits>(range: Option<ShoobyRange>, value: T) -> Result<(), ShoobyError> {
+    match T::limits(range) {
+        Some((min, max)) if value < min || value > max => Err(ShoobyError::OutOfBounds),
+        _ => Ok(()),
+    }
+}
+
+/// Generates a typed getter and setter for an integer field type, like get_int and set_num
+macro_rules! int_accessors {
+    ($variant:ident, $t:ty, $getter:ident, $setter:ident, $setter_with:ident) => {
+        pub fn $getter<T: TryFrom<$t>>(&self) -> Result<T, ShoobyError> {
+            if let ShoobyFieldType::$variant(val) = self.data {
+                val.try_into()
+                    .map_err(|_| ShoobyError::InvalidTypeConversion)
+            } else {
+                Err(ShoobyError::InvalidType)
+            }
+        }
+
+        pub fn $setter<T: TryInto<$t>>(&mut self, new_val: T) -> Result<$t, ShoobyError> {
+            self.$setter_with(new_val, &mut NoHook)
+        }
+
+        pub(crate) fn $setter_with<T: TryInto<$t>>(
+            &mut self,
+            new_val: T,
+            hook: &mut dyn BeforeChange<ID>,
+        ) -> Result<$t, ShoobyError> {
+            let value: $t = new_val
+                .try_into()
+                .map_err(|_| ShoobyError::InvalidTypeConversion)?;
+
+            if let ShoobyFieldType::$variant(old_value) = self.data {
+                check_range(self.meta.range, value)?;
+
+                if old_value != value {
+                    hook.before_change(self, &ShoobyValue::$variant(value))?;
+                    self.data = ShoobyFieldType::$variant(value);
+                    self.has_changed = true;
+                }
+
+                Ok(old_value)
+            } else {
+                Err(ShoobyError::InvalidType)
+            }
+        }
+    };
+}
+
+// The header that prefixes every persisted field:
+// | layout fingerprint: u32 | value length: u16 | type tag: u8 | crc32: u32 |
+// the crc covers the whole record except itself, all numbers are little endian
+const RECORD_LEN_OFFSET: usize = 4;
+const RECORD_TAG_OFFSET: usize = 6;
+const RECORD_CRC_OFFSET: usize = 7;
+pub(crate) const RECORD_HEADER_SIZE: usize = 11;
+
+fn record_crc(record: &[u8]) -> u32 {
+    let crc = crc32_update(CRC32_INIT, &record[..RECORD_CRC_OFFSET]);
+    !crc32_update(crc, &record[RECORD_HEADER_SIZE..])
+}
+
+/// Used by the shooby_db! macro to size the storage scratch buffer by the biggest field
+pub(crate) const fn record_size(data_sizes: &[usize]) -> usize {
+    let mut max = 0;
+    let mut i = 0;
+    while i < data_sizes.len() {
+        assert!(
+            data_sizes[i] <= u16::MAX as usize,
+            "shooby_db: field size is limited to u16::MAX bytes"
+        );
+        if data_sizes[i] > max {
+            max = data_sizes[i];
+        }
+        i += 1;
+    }
+    RECORD_HEADER_SIZE + max
+}
+
+/// Used by the shooby_db! macro to check the size of a blob when its type is not given
+pub(crate) const fn size_of_value<T>(_: &T) -> usize {
+    size_of::<T>()
+}
+
 pub const PERSISTENT: bool = true;
 pub const NON_PERSISTENT: bool = false;
 
-/// ShoobyField
-/// This is the fields that are held for each item in the database
-#[derive(Debug)]
-pub struct ShoobyField<ID> {
+/// The source of a field value after the DB was initialized
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadStatus {
+    /// The value was loaded from the persistent storage
+    Loaded,
+    /// The field is not persistent or was never saved, the default value is used
+    Default,
+    /// The stored value could not be loaded or was invalid, the default value is used
+    Corrupted,
+    /// The field was stored with a different type or size, the default value is used
+    LayoutChanged,
+}
+
+/// The type of a field, as declared in shooby_db!
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShoobyKind {
+    Bool,
+    Int,
+    U32,
+    I64,
+    U64,
+    Float,
+    Enum,
+    String,
+    Blob,
+}
+
+/// The declaration of a field in shooby_db!, shared by all the DB instances.
+/// shooby_db! generates NAME::SCHEMA with the fields in ID order, so tools can walk any DB
+///     `for meta in NAME::SCHEMA.iter() { println!("{}: {:?} = {}", meta.name(), meta.kind(), meta.default()); }`
+pub struct FieldMeta<ID: 'static> {
     id: ID,
-    data: ShoobyFieldType,
-    range: Option<(i32, i32)>,
-    pub(crate) persistent: bool,
-    pub(crate) has_changed: bool,
+    kind: ShoobyKind,
+    default: fn() -> ShoobyValue<'static>,
+    range: Option<ShoobyRange>,
+    capacity: usize,
+    persistent: bool,
+    variants: &'static [(u32, &'static str)],
 }
 
-impl<ID: AsRef<str> + Copy> ShoobyField<ID> {
+impl<ID: AsRef<str> + Copy> FieldMeta<ID> {
+    /// Used by the shooby_db! macro, the default is a fn so enums are converted by ShoobyEnum
     pub(crate) const fn new(
         id: ID,
-        data: ShoobyFieldType,
-        range: Option<(i32, i32)>,
+        kind: ShoobyKind,
+        default: fn() -> ShoobyValue<'static>,
+        range: Option<ShoobyRange>,
+        capacity: usize,
         persistent: bool,
+        variants: &'static [(u32, &'static str)],
     ) -> Self {
-        ShoobyField {
+        FieldMeta {
             id,
-            data,
+            kind,
+            default,
             range,
+            capacity,
             persistent,
+            variants,
+        }
+    }
+
+    pub fn id(&self) -> ID {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        self.id.as_ref()
+    }
+
+    /// The name without the DB prefix, `SSID` for `NAME::ID::SSID`
+    pub fn short_name(&self) -> &str {
+        short_name(self.id.as_ref())
+    }
+
+    pub fn kind(&self) -> ShoobyKind {
+        self.kind
+    }
+
+    /// The value the field gets on take and factory reset
+    pub fn default(&self) -> ShoobyValue<'static> {
+        (self.default)()
+    }
+
+    /// The limits of a numeric field, None if any value is allowed or the field is not numeric
+    pub fn range(&self) -> Option<ShoobyRange> {
+        self.range
+    }
+
+    /// The size of the value in bytes, the max length of a string or the size of a blob
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn is_persistent(&self) -> bool {
+        self.persistent
+    }
+
+    /// The discriminant and name of every variant of an Enum field, empty for other kinds
+    pub fn variants(&self) -> &'static [(u32, &'static str)] {
+        self.variants
+    }
+}
+
+impl<ID: Debug> Debug for FieldMeta<ID> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("FieldMeta")
+            .field("id", &self.id)
+            .field("kind", &self.kind)
+            .field("default", &(self.default)())
+            .field("range", &self.range)
+            .field("capacity", &self.capacity)
+            .field("persistent", &self.persistent)
+            .finish()
+    }
+}
+
+fn short_name(name: &str) -> &str {
+    name.rsplit("::").next().unwrap_or_default()
+}
+
+/// Used by the shooby_db! macro for the default of an Enum field
+pub(crate) fn enum_value<E: ShoobyEnum>(value: &E) -> ShoobyValue<'static> {
+    let discriminant = value.to_discriminant();
+    ShoobyValue::Enum(
+        discriminant,
+        variant_name(E::VARIANTS, discriminant).unwrap_or_default(),
+    )
+}
+
+/// Used by the shooby_db! macro for the default of a Blob field, its bytes as set_blob sets them.
+/// T must have no padding bytes, they are not initialized
+pub(crate) fn blob_value<T>(value: &'static T) -> ShoobyValue<'static> {
+    ShoobyValue::Blob(unsafe { any_as_u8_slice(value) })
+}
+
+/// Used by the shooby_db! macro for the variants of an Enum field
+pub(crate) const fn enum_variants<E: ShoobyEnum>(_default: &E) -> &'static [(u32, &'static str)] {
+    E::VARIANTS
+}
+
+/// The biggest blob that set_from_str can set, its bytes are decoded on the stack
+pub const TEXT_BLOB_MAX_SIZE: usize = 256;
+
+/// Asked by the _with setters right before a field gets a new value, after the value was validated.
+/// An error leaves the field as it is and is returned by the setter
+pub(crate) trait BeforeChange<ID: 'static> {
+    fn before_change(
+        &mut self,
+        field: &ShoobyField<ID>,
+        new: &ShoobyValue<'_>,
+    ) -> Result<(), ShoobyError>;
+}
+
+/// The hook of the setters of the field itself, it allows every change
+pub(crate) struct NoHook;
+
+impl<ID: 'static> BeforeChange<ID> for NoHook {
+    fn before_change(
+        &mut self,
+        _: &ShoobyField<ID>,
+        _: &ShoobyValue<'_>,
+    ) -> Result<(), ShoobyError> {
+        Ok(())
+    }
+}
+
+/// ShoobyField
+/// This is the fields that are held for each item in the database
+pub struct ShoobyField<ID: 'static> {
+    data: ShoobyFieldType,
+    meta: &'static FieldMeta<ID>,
+    pub(crate) has_changed: bool,
+}
+
+impl<ID: Debug> Debug for ShoobyField<ID> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("ShoobyField")
+            .field("id", &self.meta.id)
+            .field("data", &self.data)
+            .field("range", &self.meta.range)
+            .field("persistent", &self.meta.persistent)
+            .field("has_changed", &self.has_changed)
+            .finish()
+    }
+}
+
+impl<ID: AsRef<str> + Copy> ShoobyField<ID> {
+    pub(crate) const fn new(data: ShoobyFieldType, meta: &'static FieldMeta<ID>) -> Self {
+        ShoobyField {
+            data,
+            meta,
             has_changed: false,
         }
     }
@@ -60,11 +525,44 @@ impl<ID: AsRef<str> + Copy> ShoobyField<ID> {
     // ===================GETTERS==================
 
     pub fn id(&self) -> ID {
-        self.id
+        self.meta.id
     }
 
     pub fn name(&self) -> &str {
-        self.id.as_ref()
+        self.meta.id.as_ref()
+    }
+
+    /// The name without the DB prefix, `SSID` for `NAME::ID::SSID`
+    pub fn short_name(&self) -> &str {
+        short_name(self.meta.id.as_ref())
+    }
+
+    /// The declaration of the field in shooby_db!
+    pub fn meta(&self) -> &'static FieldMeta<ID> {
+        self.meta
+    }
+
+    pub fn kind(&self) -> ShoobyKind {
+        self.meta.kind
+    }
+
+    /// The limits of a numeric field, None if any value is allowed or the field is not numeric
+    pub fn range(&self) -> Option<ShoobyRange> {
+        self.meta.range
+    }
+
+    pub fn is_persistent(&self) -> bool {
+        self.meta.persistent
+    }
+
+    /// The size of the value in bytes, the max length of a string or the size of a blob
+    pub fn capacity(&self) -> usize {
+        self.data.data_len()
+    }
+
+    /// The value the field gets on take and factory reset
+    pub fn default(&self) -> ShoobyValue<'static> {
+        self.meta.default()
     }
 
     pub fn get_int<T: TryFrom<i32>>(&self) -> Result<T, ShoobyError> {
@@ -76,6 +574,30 @@ impl<ID: AsRef<str> + Copy> ShoobyField<ID> {
         }
     }
 
+    int_accessors!(U32, u32, get_u32, set_u32, set_u32_with);
+    int_accessors!(I64, i64, get_i64, set_i64, set_i64_with);
+    int_accessors!(U64, u64, get_u64, set_u64, set_u64_with);
+
+    pub fn get_float<T: From<f32>>(&self) -> Result<T, ShoobyError> {
+        if let ShoobyFieldType::Float(val) = self.data {
+            Ok(val.into())
+        } else {
+            Err(ShoobyError::InvalidType)
+        }
+    }
+
+    pub fn get_enum<E: ShoobyEnum>(&self) -> Result<E, ShoobyError> {
+        if let ShoobyFieldType::Enum(val, variants) = self.data {
+            if variants != E::VARIANTS {
+                return Err(ShoobyError::InvalidType);
+            }
+
+            E::from_discriminant(val).ok_or(ShoobyError::InvalidTypeConversion)
+        } else {
+            Err(ShoobyError::InvalidType)
+        }
+    }
+
     pub fn get_bool(&self) -> Result<bool, ShoobyError> {
         if let ShoobyFieldType::Bool(val) = self.data {
             Ok(val)
@@ -104,23 +626,98 @@ impl<ID: AsRef<str> + Copy> ShoobyField<ID> {
         }
     }
 
+    /// The value of the field, whatever its type is
+    pub fn value(&self) -> ShoobyValue<'_> {
+        self.value_from(None)
+    }
+
+    /// The value of the field, or the value in bytes in the format of backup
+    fn value_from<'a>(&'a self, bytes: Option<&'a [u8]>) -> ShoobyValue<'a> {
+        macro_rules! from_le {
+            ($val:expr, $t:ty) => {
+                match bytes {
+                    Some(bytes) => <$t>::from_le_bytes(bytes.try_into().unwrap_or_default()),
+                    None => *$val,
+                }
+            };
+        }
+
+        match &self.data {
+            ShoobyFieldType::Bool(val) => {
+                ShoobyValue::Bool(bytes.map_or(*val, |bytes| bytes[0] != 0))
+            }
+            ShoobyFieldType::Int(val) => ShoobyValue::Int(from_le!(val, i32)),
+            ShoobyFieldType::U32(val) => ShoobyValue::U32(from_le!(val, u32)),
+            ShoobyFieldType::I64(val) => ShoobyValue::I64(from_le!(val, i64)),
+            ShoobyFieldType::U64(val) => ShoobyValue::U64(from_le!(val, u64)),
+            ShoobyFieldType::Float(val) => ShoobyValue::Float(from_le!(val, f32)),
+            ShoobyFieldType::Enum(val, variants) => {
+                let val = from_le!(val, u32);
+                ShoobyValue::Enum(val, variant_name(variants, val).unwrap_or_default())
+            }
+            ShoobyFieldType::String(data) => {
+                ShoobyValue::String(str_from_u8_nul_utf8(bytes.unwrap_or(data)).unwrap_or_default())
+            }
+            ShoobyFieldType::Blob(data) => ShoobyValue::Blob(bytes.unwrap_or(data)),
+        }
+    }
+
+    /// The value copied by backup to the start of bytes
+    pub(crate) fn backup_value<'a>(&'a self, bytes: &'a [u8]) -> ShoobyValue<'a> {
+        self.value_from(Some(&bytes[..self.data.data_len()]))
+    }
+
     //======================SETTERS======================
+    // The setters of the field itself are not intercepted, the writer given by write_with calls the
+    // _with setters with a hook that asks the interceptor of the DB
+
     pub fn set_num<T: TryInto<i32>>(&mut self, new_val: T) -> Result<i32, ShoobyError> {
+        self.set_num_with(new_val, &mut NoHook)
+    }
+
+    pub(crate) fn set_num_with<T: TryInto<i32>>(
+        &mut self,
+        new_val: T,
+        hook: &mut dyn BeforeChange<ID>,
+    ) -> Result<i32, ShoobyError> {
         let value: i32 = new_val
             .try_into()
             .map_err(|_| ShoobyError::InvalidTypeConversion)?;
 
-        if let ShoobyFieldType::Int(ref mut data) = self.data {
-            let old_value = *data;
-            if let Some((min, max)) = self.range {
-                if value < min || value > max {
-                    println!("value {} is out of bounds range {} - {}", value, min, max);
-                    return Err(ShoobyError::OutOfBounds);
-                }
+        if let ShoobyFieldType::Int(old_value) = self.data {
+            check_range(self.meta.range, value)?;
+
+            if old_value != value {
+                hook.before_change(self, &ShoobyValue::Int(value))?;
+                self.data = ShoobyFieldType::Int(value);
+                self.has_changed = true;
             }
 
-            if *data != value {
-                *data = value;
+            Ok(old_value)
+        } else {
+            Err(ShoobyError::InvalidType)
+        }
+    }
+
+    /// Sets a float value, NaN is never accepted
+    pub fn set_float(&mut self, value: f32) -> Result<f32, ShoobyError> {
+        self.set_float_with(value, &mut NoHook)
+    }
+
+    pub(crate) fn set_float_with(
+        &mut self,
+        value: f32,
+        hook: &mut dyn BeforeChange<ID>,
+    ) -> Result<f32, ShoobyError> {
+        if let ShoobyFieldType::Float(old_value) = self.data {
+            if value.is_nan() {
+                return Err(ShoobyError::OutOfBounds);
+            }
+            check_range(self.meta.range, value)?;
+
+            if old_value != value {
+                hook.before_change(self, &ShoobyValue::Float(value))?;
+                self.data = ShoobyFieldType::Float(value);
                 self.has_changed = true;
             }
 
@@ -130,11 +727,61 @@ impl<ID: AsRef<str> + Copy> ShoobyField<ID> {
         }
     }
 
+    pub fn set_enum<E: ShoobyEnum>(&mut self, new_val: E) -> Result<E, ShoobyError> {
+        self.set_enum_with(new_val, &mut NoHook)
+    }
+
+    pub(crate) fn set_enum_with<E: ShoobyEnum>(
+        &mut self,
+        new_val: E,
+        hook: &mut dyn BeforeChange<ID>,
+    ) -> Result<E, ShoobyError> {
+        if let ShoobyFieldType::Enum(old_value, variants) = self.data {
+            if variants != E::VARIANTS {
+                return Err(ShoobyError::InvalidType);
+            }
+
+            self.set_discriminant_with(new_val.to_discriminant(), hook)?;
+            E::from_discriminant(old_value).ok_or(ShoobyError::InvalidTypeConversion)
+        } else {
+            Err(ShoobyError::InvalidType)
+        }
+    }
+
+    /// Sets an Enum field to the variant with the discriminant, OutOfBounds if there is none
+    pub(crate) fn set_discriminant_with(
+        &mut self,
+        value: u32,
+        hook: &mut dyn BeforeChange<ID>,
+    ) -> Result<(), ShoobyError> {
+        if let ShoobyFieldType::Enum(old_value, variants) = self.data {
+            let name = variant_name(variants, value).ok_or(ShoobyError::OutOfBounds)?;
+
+            if old_value != value {
+                hook.before_change(self, &ShoobyValue::Enum(value, name))?;
+                self.data = ShoobyFieldType::Enum(value, variants);
+                self.has_changed = true;
+            }
+
+            Ok(())
+        } else {
+            Err(ShoobyError::InvalidType)
+        }
+    }
+
     pub fn set_bool(&mut self, new_val: bool) -> Result<bool, ShoobyError> {
-        if let ShoobyFieldType::Bool(ref mut data) = self.data {
-            let old_value = *data;
-            if *data != new_val {
-                *data = new_val;
+        self.set_bool_with(new_val, &mut NoHook)
+    }
+
+    pub(crate) fn set_bool_with(
+        &mut self,
+        new_val: bool,
+        hook: &mut dyn BeforeChange<ID>,
+    ) -> Result<bool, ShoobyError> {
+        if let ShoobyFieldType::Bool(old_value) = self.data {
+            if old_value != new_val {
+                hook.before_change(self, &ShoobyValue::Bool(new_val))?;
+                self.data = ShoobyFieldType::Bool(new_val);
                 self.has_changed = true;
             }
             Ok(old_value)
@@ -144,99 +791,344 @@ impl<ID: AsRef<str> + Copy> ShoobyField<ID> {
     }
 
     pub fn set_string(&mut self, new_str: &str) -> Result<(), ShoobyError> {
-        if let ShoobyFieldType::String(ref mut data) = self.data {
+        self.set_string_with(new_str, &mut NoHook)
+    }
+
+    pub(crate) fn set_string_with(
+        &mut self,
+        new_str: &str,
+        hook: &mut dyn BeforeChange<ID>,
+    ) -> Result<(), ShoobyError> {
+        let changed = if let ShoobyFieldType::String(ref data) = self.data {
             if data.len() < new_str.len() {
                 return Err(ShoobyError::OutOfBounds);
             }
 
-            let old_str =
-                str_from_u8_nul_utf8(data).unwrap_or_else(|_| panic!("Invalid UTF-8 as string"));
+            // an invalid old value is always replaced
+            str_from_u8_nul_utf8(data) != Ok(new_str)
+        } else {
+            return Err(ShoobyError::InvalidType);
+        };
 
-            if old_str != new_str {
+        if changed {
+            hook.before_change(self, &ShoobyValue::String(new_str))?;
+            if let ShoobyFieldType::String(ref mut data) = self.data {
                 data[0..new_str.len()].copy_from_slice(new_str.as_bytes());
                 data[new_str.len()..].fill(0);
-                self.has_changed = true;
             }
-            Ok(())
-        } else {
-            Err(ShoobyError::InvalidType)
+            self.has_changed = true;
         }
+        Ok(())
     }
 
+    /// Sets a Blob field from a value of its type, InvalidSize if T is not the blob size.
+    /// T must have no padding bytes (`#[repr(C, packed)]` or only fields of one size), they are not initialized
     pub fn set_blob<T: Sized>(&mut self, new_blob: &T) -> Result<(), ShoobyError> {
-        if let ShoobyFieldType::Blob(ref mut data) = self.data {
-            assert!(data.len() == size_of::<T>());
-            let new_blob_slice = unsafe { any_as_u8_slice(new_blob) };
-            if *data != new_blob_slice {
-                data.copy_from_slice(new_blob_slice);
-                self.has_changed = true;
-            }
+        self.set_blob_with(new_blob, &mut NoHook)
+    }
 
-            Ok(())
+    pub(crate) fn set_blob_with<T: Sized>(
+        &mut self,
+        new_blob: &T,
+        hook: &mut dyn BeforeChange<ID>,
+    ) -> Result<(), ShoobyError> {
+        // set_blob_bytes_with checks the size
+        self.set_blob_bytes_with(unsafe { any_as_u8_slice(new_blob) }, hook)
+    }
+
+    /// Sets a Blob field from its bytes, InvalidSize if their number is not the blob size
+    pub fn set_blob_bytes(&mut self, new_blob: &[u8]) -> Result<(), ShoobyError> {
+        self.set_blob_bytes_with(new_blob, &mut NoHook)
+    }
+
+    pub(crate) fn set_blob_bytes_with(
+        &mut self,
+        new_blob: &[u8],
+        hook: &mut dyn BeforeChange<ID>,
+    ) -> Result<(), ShoobyError> {
+        let changed = if let ShoobyFieldType::Blob(ref data) = self.data {
+            if data.len() != new_blob.len() {
+                return Err(ShoobyError::InvalidSize);
+            }
+            *data != new_blob
         } else {
-            Err(ShoobyError::InvalidType)
+            return Err(ShoobyError::InvalidType);
+        };
+
+        if changed {
+            hook.before_change(self, &ShoobyValue::Blob(new_blob))?;
+            if let ShoobyFieldType::Blob(ref mut data) = self.data {
+                data.copy_from_slice(new_blob);
+            }
+            self.has_changed = true;
         }
+
+        Ok(())
+    }
+
+    //======================TEXT======================
+
+    /// Sets the field from text, with the same checks as the typed setters.
+    /// Numbers are decimal, bools are `true` or `false`, enums are the variant name or discriminant
+    /// and blobs are hex, two digits per byte (up to TEXT_BLOB_MAX_SIZE bytes).
+    /// Text that can't be parsed for the field type returns InvalidTypeConversion
+    ///     `writer.field_mut(NAME::ID::RETRIES).set_from_str("5")?;`
+    pub fn set_from_str(&mut self, text: &str) -> Result<(), ShoobyError> {
+        self.set_from_str_with(text, &mut NoHook)
+    }
+
+    pub(crate) fn set_from_str_with(
+        &mut self,
+        text: &str,
+        hook: &mut dyn BeforeChange<ID>,
+    ) -> Result<(), ShoobyError> {
+        fn parse<T: core::str::FromStr>(text: &str) -> Result<T, ShoobyError> {
+            text.parse().map_err(|_| ShoobyError::InvalidTypeConversion)
+        }
+
+        match self.data {
+            ShoobyFieldType::Bool(_) => self.set_bool_with(parse(text)?, hook).map(|_| ()),
+            ShoobyFieldType::Int(_) => self.set_num_with(parse::<i32>(text)?, hook).map(|_| ()),
+            ShoobyFieldType::U32(_) => self.set_u32_with(parse::<u32>(text)?, hook).map(|_| ()),
+            ShoobyFieldType::I64(_) => self.set_i64_with(parse::<i64>(text)?, hook).map(|_| ()),
+            ShoobyFieldType::U64(_) => self.set_u64_with(parse::<u64>(text)?, hook).map(|_| ()),
+            ShoobyFieldType::Float(_) => self.set_float_with(parse(text)?, hook).map(|_| ()),
+            ShoobyFieldType::Enum(_, variants) => {
+                let value = match variants.iter().find(|(_, name)| *name == text) {
+                    Some((value, _)) => *value,
+                    None => parse(text)?,
+                };
+                self.set_discriminant_with(value, hook)
+            }
+            ShoobyFieldType::String(_) => self.set_string_with(text, hook),
+            ShoobyFieldType::Blob(ref data) => {
+                let mut buffer = [0u8; TEXT_BLOB_MAX_SIZE];
+                let bytes = buffer
+                    .get_mut(..data.len())
+                    .ok_or(ShoobyError::InvalidSize)?;
+                hex_decode(text, bytes)?;
+                self.set_blob_bytes_with(bytes, hook)
+            }
+        }
+    }
+
+    /// Writes the value as text, in the format set_from_str reads
+    pub fn write_value_to<W: core::fmt::Write>(&self, out: &mut W) -> FmtResult {
+        write!(out, "{}", self.value())
     }
 
     //===============================PERSISTENCE===============================
+    // A persisted field is a record header followed by the value.
+    // numeric values are persisted as fixed width little endian
+
+    /// A fingerprint of the field type and size, changes when the field layout changes
+    pub(crate) fn layout_fingerprint(&self) -> u32 {
+        let hash = fnv1a(FNV_OFFSET_BASIS, &[self.data.type_tag()]);
+        fnv1a(hash, &(self.data.data_len() as u32).to_le_bytes())
+    }
+
+    /// Writes the value bytes to the start of out and returns their length
+    fn encode(&self, out: &mut [u8]) -> usize {
+        let data: &[u8] = match &self.data {
+            ShoobyFieldType::Int(val) => &val.to_le_bytes(),
+            ShoobyFieldType::U32(val) => &val.to_le_bytes(),
+            ShoobyFieldType::I64(val) => &val.to_le_bytes(),
+            ShoobyFieldType::U64(val) => &val.to_le_bytes(),
+            ShoobyFieldType::Float(val) => &val.to_le_bytes(),
+            ShoobyFieldType::Enum(val, _) => &val.to_le_bytes(),
+            ShoobyFieldType::Bool(val) => &[*val as u8],
+            ShoobyFieldType::String(data) => data,
+            ShoobyFieldType::Blob(data) => data,
+        };
+
+        out[..data.len()].copy_from_slice(data);
+        data.len()
+    }
+
+    /// Validates the value bytes and assigns them, the field is not changed on error
+    fn decode(&mut self, bytes: &[u8]) -> Result<(), ShoobyError> {
+        macro_rules! decode_le {
+            ($val:expr, $t:ty) => {{
+                let loaded_val =
+                    <$t>::from_le_bytes(bytes.try_into().map_err(|_| ShoobyError::InvalidSize)?);
+                check_range(self.meta.range, loaded_val)?;
+                *$val = loaded_val;
+            }};
+        }
+
+        if bytes.len() != self.data.data_len() {
+            return Err(ShoobyError::InvalidSize);
+        }
+
+        match &mut self.data {
+            ShoobyFieldType::Int(ref mut val) => decode_le!(val, i32),
+            ShoobyFieldType::U32(ref mut val) => decode_le!(val, u32),
+            ShoobyFieldType::I64(ref mut val) => decode_le!(val, i64),
+            ShoobyFieldType::U64(ref mut val) => decode_le!(val, u64),
+            ShoobyFieldType::Float(ref mut val) => {
+                let loaded_val =
+                    f32::from_le_bytes(bytes.try_into().map_err(|_| ShoobyError::InvalidSize)?);
+                if loaded_val.is_nan() {
+                    return Err(ShoobyError::OutOfBounds);
+                }
+                check_range(self.meta.range, loaded_val)?;
+                *val = loaded_val;
+            }
+            ShoobyFieldType::Enum(ref mut val, variants) => {
+                let loaded_val =
+                    u32::from_le_bytes(bytes.try_into().map_err(|_| ShoobyError::InvalidSize)?);
+                if variant_name(variants, loaded_val).is_none() {
+                    return Err(ShoobyError::OutOfBounds);
+                }
+                *val = loaded_val;
+            }
+            ShoobyFieldType::Bool(ref mut val) => {
+                *val = match bytes[0] {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(ShoobyError::InvalidTypeConversion),
+                };
+            }
+            ShoobyFieldType::String(data) => {
+                if str_from_u8_nul_utf8(bytes).is_err() {
+                    return Err(ShoobyError::InvalidTypeConversion);
+                }
+                data.copy_from_slice(bytes);
+            }
+            ShoobyFieldType::Blob(data) => data.copy_from_slice(bytes),
+        }
+
+        Ok(())
+    }
+
+    /// The size of the value in bytes
+    pub(crate) fn data_len(&self) -> usize {
+        self.data.data_len()
+    }
+
+    /// The tag of the field type, as in the record header
+    pub(crate) fn type_tag(&self) -> u8 {
+        self.data.type_tag()
+    }
+
+    /// Sets the field from value bytes in the persisted format, with the same checks as the typed setters
+    pub(crate) fn set_from_bytes_with(
+        &mut self,
+        bytes: &[u8],
+        hook: &mut dyn BeforeChange<ID>,
+    ) -> Result<(), ShoobyError> {
+        macro_rules! from_le {
+            ($t:ty) => {
+                <$t>::from_le_bytes(bytes.try_into().map_err(|_| ShoobyError::InvalidSize)?)
+            };
+        }
+
+        if bytes.len() != self.data.data_len() {
+            return Err(ShoobyError::InvalidSize);
+        }
+
+        match self.data {
+            ShoobyFieldType::Bool(_) => match bytes[0] {
+                0 => self.set_bool_with(false, hook).map(|_| ()),
+                1 => self.set_bool_with(true, hook).map(|_| ()),
+                _ => Err(ShoobyError::InvalidTypeConversion),
+            },
+            ShoobyFieldType::Int(_) => self.set_num_with(from_le!(i32), hook).map(|_| ()),
+            ShoobyFieldType::U32(_) => self.set_u32_with(from_le!(u32), hook).map(|_| ()),
+            ShoobyFieldType::I64(_) => self.set_i64_with(from_le!(i64), hook).map(|_| ()),
+            ShoobyFieldType::U64(_) => self.set_u64_with(from_le!(u64), hook).map(|_| ()),
+            ShoobyFieldType::Float(_) => self.set_float_with(from_le!(f32), hook).map(|_| ()),
+            ShoobyFieldType::Enum(_, _) => self.set_discriminant_with(from_le!(u32), hook),
+            ShoobyFieldType::String(_) => {
+                let text =
+                    str_from_u8_nul_utf8(bytes).map_err(|_| ShoobyError::InvalidTypeConversion)?;
+                self.set_string_with(text, hook)
+            }
+            ShoobyFieldType::Blob(_) => self.set_blob_bytes_with(bytes, hook),
+        }
+    }
 
+    /// Copies the value to the start of out so it can be restored later, returns its length
+    pub(crate) fn backup(&self, out: &mut [u8]) -> usize {
+        self.encode(out)
+    }
+
+    /// Puts back the value copied by backup to the start of bytes, returns its length.
+    /// The changed flag is left as is
+    pub(crate) fn restore(&mut self, bytes: &[u8]) -> usize {
+        let len = self.data.data_len();
+        // a backup holds a value that was valid for the field, it can't fail
+        let _ = self.decode(&bytes[..len]);
+        len
+    }
+
+    /// Fills the record header of this field, the value must already be after the header
+    pub(crate) fn seal_record(&self, record: &mut [u8]) {
+        let value_len = (record.len() - RECORD_HEADER_SIZE) as u16;
+        record[..RECORD_LEN_OFFSET].copy_from_slice(&self.layout_fingerprint().to_le_bytes());
+        record[RECORD_LEN_OFFSET..RECORD_TAG_OFFSET].copy_from_slice(&value_len.to_le_bytes());
+        record[RECORD_TAG_OFFSET] = self.data.type_tag();
+        let crc = record_crc(record);
+        record[RECORD_CRC_OFFSET..RECORD_HEADER_SIZE].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    /// Saves the field to the storage
+    /// scratch: a buffer large enough for the record of the biggest field in the DB
     pub(crate) fn save<Storage: ShoobyStorage<ID = ID>>(
         &self,
         storage: &Storage,
+        scratch: &mut [u8],
     ) -> Result<(), ShoobyError> {
-        if !self.persistent {
+        if !self.meta.persistent {
             return Ok(());
         }
 
-        let data = match &self.data {
-            ShoobyFieldType::Int(ref val) => unsafe { any_as_u8_slice(val) },
-            ShoobyFieldType::Bool(ref val) => unsafe { any_as_u8_slice(val) },
-            ShoobyFieldType::String(data) => data,
-            ShoobyFieldType::Blob(data) => data,
-        };
+        let len = self.encode(&mut scratch[RECORD_HEADER_SIZE..]);
+        let record = &mut scratch[..RECORD_HEADER_SIZE + len];
+        self.seal_record(record);
 
-        storage.save_raw(self.id, data)
+        storage.save_raw(self.meta.id, record)
     }
 
+    /// Loads the field value from the storage.
+    /// scratch: a buffer large enough for the record of the biggest field in the DB
+    /// returns: true if the value was found in the storage, false if the field is not persistent
+    /// or nothing was stored for it.
+    /// ShoobyError::CorruptedRecord is returned if the record failed the crc check.
+    /// ShoobyError::LayoutChanged is returned if the field was saved with a different type or size.
+    /// Any other error means the storage failed or the stored value is not valid for this field.
+    /// The field value is not changed on error
     pub(crate) fn load<Storage: ShoobyStorage<ID = ID>>(
         &mut self,
         storage: &mut Storage,
+        scratch: &mut [u8],
     ) -> Result<bool, ShoobyError> {
-        if !self.persistent {
+        if !self.meta.persistent {
             return Ok(false);
         }
 
-        let res = match &mut self.data {
-            ShoobyFieldType::Int(ref mut val) => {
-                let mut data = [0; size_of::<i32>()];
-                let loaded = storage.load_raw(self.id, &mut data)?;
-                if loaded {
-                    *val = unsafe { *u8_slice_as_any(&data) };
-                    true
-                } else {
-                    false
-                }
-            }
-            ShoobyFieldType::Bool(ref mut val) => {
-                let mut data = [0; size_of::<bool>()];
-                let loaded = storage.load_raw(self.id, &mut data)?;
-                if loaded {
-                    *val = unsafe { *u8_slice_as_any(&data) };
-                    true
-                } else {
-                    false
-                }
-            }
-            ShoobyFieldType::String(data) | ShoobyFieldType::Blob(data) => {
-                let loaded = storage.load_raw(self.id, *data)?;
-                if loaded {
-                    true
-                } else {
-                    false
-                }
-            }
-        };
+        let record = &mut scratch[..RECORD_HEADER_SIZE + self.data.data_len()];
+        match storage.load_raw(self.meta.id, record) {
+            Ok(true) => {}
+            Ok(false) => return Ok(false),
+            Err(ShoobyError::InvalidSize) => return Err(ShoobyError::LayoutChanged),
+            Err(err) => return Err(err),
+        }
+
+        let (header, value) = record.split_at(RECORD_HEADER_SIZE);
+        if header[RECORD_CRC_OFFSET..] != record_crc(record).to_le_bytes() {
+            return Err(ShoobyError::CorruptedRecord);
+        }
+
+        let value_len = (value.len() as u16).to_le_bytes();
+        if header[..RECORD_LEN_OFFSET] != self.layout_fingerprint().to_le_bytes()
+            || header[RECORD_LEN_OFFSET..RECORD_TAG_OFFSET] != value_len
+            || header[RECORD_TAG_OFFSET] != self.data.type_tag()
+        {
+            return Err(ShoobyError::LayoutChanged);
+        }
 
-        Ok(res)
+        self.decode(value)?;
+        Ok(true)
     }
 }
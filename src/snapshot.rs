@@ -0,0 +1,192 @@
+//! A compact binary snapshot of all the fields of a DB, to back up and restore it or to move it to another device.
+//!
+//! Layout, all numbers are little endian:
+//! | magic "SHBY" | format version: u8 | name length: u8 | DB name | schema hash: u32 | record count: u16 |
+//! | records... | crc32 of everything before it: u32 |
+//! Every field is a TLV record, keyed by the hash of its short name:
+//! | field name hash: u32 | type tag: u8 | value length: u16 | value |
+//! Values are encoded as they are persisted. The schema hash covers the name, type and size of every field,
+//! so a snapshot can only be imported into a DB with the same name and fields.
+//! Records are matched to the fields by their name hash, a record of no field or of another type or size is
+//! an error of that record only, the other records are still checked.
+
+use crate::errors::ShoobyError;
+use crate::shooby_field::ShoobyField;
+use crate::traits::ShoobyId;
+use crate::utils::{crc32_update, fnv1a, CRC32_INIT, FNV_OFFSET_BASIS};
+use crate::writer::ShoobyWriter;
+
+const MAGIC: &[u8; 4] = b"SHBY";
+/// The version of the snapshot layout, snapshots of other versions are rejected
+pub const SNAPSHOT_FORMAT_VERSION: u8 = 1;
+
+// magic, format version and name length, the name follows
+const PREFIX_SIZE: usize = 6;
+// schema hash and record count, after the name
+const SCHEMA_SIZE: usize = 6;
+const FIELD_HEADER_SIZE: usize = 7;
+const CRC_SIZE: usize = 4;
+
+/// Used by the shooby_db! macro to size a snapshot of the DB
+#[doc(hidden)]
+pub const fn snapshot_size(name: &str, data_sizes: &[usize]) -> usize {
+    let mut size = PREFIX_SIZE + name.len() + SCHEMA_SIZE + CRC_SIZE;
+    let mut i = 0;
+    while i < data_sizes.len() {
+        size += FIELD_HEADER_SIZE + data_sizes[i];
+        i += 1;
+    }
+    size
+}
+
+/// The stable key of a field in a snapshot, the hash of its short name
+fn name_hash<ID: AsRef<str> + Copy>(field: &ShoobyField<ID>) -> u32 {
+    fnv1a(FNV_OFFSET_BASIS, field.short_name().as_bytes())
+}
+
+/// A hash of the name, type and size of every field, by ID order
+pub(crate) fn schema_hash<ID: AsRef<str> + Copy>(fields: &[ShoobyField<ID>]) -> u32 {
+    fields.iter().fold(FNV_OFFSET_BASIS, |hash, field| {
+        let hash = fnv1a(hash, field.short_name().as_bytes());
+        fnv1a(hash, &field.layout_fingerprint().to_le_bytes())
+    })
+}
+
+/// Used by DB::export_to, writes the snapshot of the fields to the start of out and returns its size.
+/// InvalidSize if out is too small or the name longer than 255 bytes
+#[doc(hidden)]
+pub fn export_snapshot<ID: AsRef<str> + Copy>(
+    name: &str,
+    fields: &[ShoobyField<ID>],
+    out: &mut [u8],
+) -> Result<usize, ShoobyError> {
+    let mut writer = SliceWriter { out, pos: 0 };
+    writer.put(MAGIC)?;
+    let name_len = u8::try_from(name.len()).map_err(|_| ShoobyError::InvalidSize)?;
+    writer.put(&[SNAPSHOT_FORMAT_VERSION, name_len])?;
+    writer.put(name.as_bytes())?;
+    writer.put(&schema_hash(fields).to_le_bytes())?;
+    writer.put(&(fields.len() as u16).to_le_bytes())?;
+
+    for field in fields {
+        let len = field.data_len();
+        writer.put(&name_hash(field).to_le_bytes())?;
+        writer.put(&[field.type_tag()])?;
+        writer.put(&(len as u16).to_le_bytes())?;
+        let value = writer.reserve(len)?;
+        field.backup(value);
+    }
+
+    let crc = !crc32_update(CRC32_INIT, &writer.out[..writer.pos]);
+    writer.put(&crc.to_le_bytes())?;
+    Ok(writer.pos)
+}
+
+/// Used by DB::import_from, sets the fields from a snapshot through the validating setters and returns
+/// the error of the first invalid record: UnknownField for a name hash of no field, InvalidType or
+/// InvalidSize for a value of another type or size, or the error of the setter.
+/// A snapshot of another DB or schema returns LayoutChanged, a damaged one CorruptedRecord and one that
+/// can't be parsed InvalidFormat
+#[doc(hidden)]
+pub fn import_snapshot<ID: ShoobyId>(
+    name: &str,
+    writer: &mut ShoobyWriter<'_, ID>,
+    snapshot: &[u8],
+) -> Result<(), ShoobyError> {
+    let body_len = snapshot
+        .len()
+        .checked_sub(CRC_SIZE)
+        .ok_or(ShoobyError::InvalidFormat)?;
+    let (body, crc) = snapshot.split_at(body_len);
+    let mut reader = SliceReader { data: body, pos: 0 };
+
+    if reader.take(MAGIC.len())? != MAGIC || reader.u8()? != SNAPSHOT_FORMAT_VERSION {
+        return Err(ShoobyError::InvalidFormat);
+    }
+    if crc != (!crc32_update(CRC32_INIT, body)).to_le_bytes() {
+        return Err(ShoobyError::CorruptedRecord);
+    }
+
+    let name_len = reader.u8()? as usize;
+    if reader.take(name_len)? != name.as_bytes() || reader.u32()? != schema_hash(writer) {
+        return Err(ShoobyError::LayoutChanged);
+    }
+    if reader.u16()? as usize != writer.len() {
+        return Err(ShoobyError::InvalidFormat);
+    }
+
+    let mut first_error = None;
+    for _ in 0..writer.len() {
+        let hash = reader.u32()?;
+        let tag = reader.u8()?;
+        let len = reader.u16()? as usize;
+        let value = reader.take(len)?;
+
+        let result = match writer.iter_mut().find(|field| name_hash(field) == hash) {
+            Some(field) if field.type_tag() != tag => Err(ShoobyError::InvalidType),
+            Some(field) if field.data_len() != len => Err(ShoobyError::InvalidSize),
+            Some(mut field) => field.set_from_bytes(value),
+            None => Err(ShoobyError::UnknownField),
+        };
+        if let Err(err) = result {
+            first_error.get_or_insert(err);
+        }
+    }
+
+    if reader.pos != body.len() {
+        return Err(ShoobyError::InvalidFormat);
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+struct SliceWriter<'a> {
+    out: &'a mut [u8],
+    pos: usize,
+}
+
+impl SliceWriter<'_> {
+    fn reserve(&mut self, len: usize) -> Result<&mut [u8], ShoobyError> {
+        let bytes = self
+            .out
+            .get_mut(self.pos..self.pos + len)
+            .ok_or(ShoobyError::InvalidSize)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn put(&mut self, bytes: &[u8]) -> Result<(), ShoobyError> {
+        self.reserve(bytes.len())?.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Reads from the snapshot, reading past its end is InvalidFormat
+struct SliceReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> SliceReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ShoobyError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or(ShoobyError::InvalidFormat)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, ShoobyError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ShoobyError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, ShoobyError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
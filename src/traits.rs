@@ -1,9 +1,112 @@
-use crate::{errors::ShoobyError, ShoobyField};
+use crate::{errors::ShoobyError, ShoobyChange, ShoobyField, ShoobyValue, ShoobyWriter};
 
+/// Notified by the DB on every changed field after a write.
+/// update gets the changed field, override on_change to get its previous value as well
 pub trait ShoobyObserver {
-    type ID;
+    type ID: 'static;
+
+    /// Fields this observer is not interested in are not passed to it. All fields by default,
+    /// an IdSet generated by shooby_db! can be used to choose fields
+    ///     `fn is_interested(&self, id: &Self::ID) -> bool { self.fields.contains(*id) }`
+    fn is_interested(&self, _id: &Self::ID) -> bool {
+        true
+    }
 
+    /// Called with the field after it was changed
     fn update(&self, field: &ShoobyField<Self::ID>);
+
+    /// Called with the old and new values of the changed field, calls update by default
+    fn on_change(&self, change: &ShoobyChange<'_, Self::ID>) {
+        self.update(change.field);
+    }
+}
+
+/// A DB can notify an observer it does not own, like a multi observer that its observers unsubscribe from
+impl<T: ShoobyObserver + ?Sized> ShoobyObserver for &T {
+    type ID = T::ID;
+
+    fn is_interested(&self, id: &Self::ID) -> bool {
+        (**self).is_interested(id)
+    }
+
+    fn update(&self, field: &ShoobyField<Self::ID>) {
+        (**self).update(field)
+    }
+
+    fn on_change(&self, change: &ShoobyChange<'_, Self::ID>) {
+        (**self).on_change(change)
+    }
+}
+
+/// Called inside write_with before a field gets a new value, can reject it.
+/// The setter then returns the error, the field keeps its value and nothing is saved or notified for it.
+/// Values assigned by factory_reset, init and migrations are not intercepted.
+/// The DB owns its interceptor, given by DB::with_interceptor
+///     `Err(ShoobyError::Rejected("password too short"))`
+pub trait ShoobyInterceptor {
+    type ID: 'static;
+
+    fn before_write(
+        &self,
+        field: &ShoobyField<Self::ID>,
+        new: &ShoobyValue<'_>,
+    ) -> Result<(), ShoobyError>;
+}
+
+/// Implemented by the ID enum generated by shooby_db!, lets generic code map IDs to indexes
+pub trait ShoobyId: Copy + PartialEq + AsRef<str> + 'static {
+    /// The number of fields in the DB
+    const FIELD_COUNT: usize;
+    /// An ID of no field, the key of the schema version for storages that keep it with save_raw
+    const SCHEMA_VERSION_KEY: Self;
+
+    /// The index of the field in the DB
+    fn index(&self) -> usize;
+    /// The ID of the field at index, None if out of range
+    fn from_index(index: usize) -> Option<Self>;
+    /// The ID of the field named name, either short (`SSID`) or qualified (`NAME::ID::SSID`)
+    fn from_name(name: &str) -> Option<Self>;
+}
+
+/// Lookup of fields by name, for the fields given by reader and to migrations.
+/// Names are parsed by ShoobyId::from_name. The writer of write_with has its own field_by_name_mut
+///     `db.reader().field_by_name("SSID")`
+pub trait ShoobyFieldsByName {
+    type ID: ShoobyId;
+
+    fn field_by_name(&self, name: &str) -> Option<&ShoobyField<Self::ID>>;
+    fn field_by_name_mut(&mut self, name: &str) -> Option<&mut ShoobyField<Self::ID>>;
+}
+
+impl<ID: ShoobyId> ShoobyFieldsByName for [ShoobyField<ID>] {
+    type ID = ID;
+
+    fn field_by_name(&self, name: &str) -> Option<&ShoobyField<ID>> {
+        self.get(ID::from_name(name)?.index())
+    }
+
+    fn field_by_name_mut(&mut self, name: &str) -> Option<&mut ShoobyField<ID>> {
+        self.get_mut(ID::from_name(name)?.index())
+    }
+}
+
+/// Implemented by the DB generated by shooby_db!, lets generic code (like SharedDB) read and write any DB
+pub trait ShoobyDb {
+    type ID: ShoobyId;
+
+    /// Get the DB array of fields to read from
+    fn reader(&self) -> &[ShoobyField<Self::ID>];
+    /// Write to the DB fields inside a closure, see the generated DB::write_with
+    fn write_with<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut ShoobyWriter<'_, Self::ID>);
+    /// Write to the DB fields inside a closure that can fail, see the generated DB::try_write_with
+    fn try_write_with<T, E, F>(&mut self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut ShoobyWriter<'_, Self::ID>) -> Result<T, E>,
+        E: From<ShoobyError>;
+    /// Reset all fields to default and save them, see the generated DB::factory_reset
+    fn factory_reset(&mut self) -> Result<(), ShoobyError>;
 }
 
 pub trait ShoobyStorage {
@@ -11,5 +114,48 @@ pub trait ShoobyStorage {
     // TODO: add a generic way to give errors..
 
     fn save_raw(&self, id: Self::ID, data: &[u8]) -> Result<(), ShoobyError>;
+    /// Loads the data saved for id, returns false if nothing was saved.
+    /// If the saved data size differs from data.len() ShoobyError::InvalidSize should be returned
     fn load_raw(&mut self, id: Self::ID, data: &mut [u8]) -> Result<bool, ShoobyError>;
+
+    /// Saves the schema version of the DB, called by DB::init after loading.
+    /// By default it is saved with save_raw under ShoobyId::SCHEMA_VERSION_KEY, as a little endian u32
+    fn save_schema_version(&self, version: u32) -> Result<(), ShoobyError>
+    where
+        Self::ID: ShoobyId,
+    {
+        self.save_raw(Self::ID::SCHEMA_VERSION_KEY, &version.to_le_bytes())
+    }
+
+    /// Loads the schema version saved by save_schema_version, None if it was never saved
+    fn load_schema_version(&mut self) -> Result<Option<u32>, ShoobyError>
+    where
+        Self::ID: ShoobyId,
+    {
+        let mut version = [0u8; 4];
+        let loaded = self.load_raw(Self::ID::SCHEMA_VERSION_KEY, &mut version)?;
+        Ok(loaded.then(|| u32::from_le_bytes(version)))
+    }
+
+    /// Called by the DB before it saves the changed fields of one write. A storage may hold the saves
+    /// until commit_batch, so all the fields of a write are saved together or not at all
+    fn begin_batch(&self) {}
+
+    /// Called after all the changed fields of one write were saved
+    fn commit_batch(&self) -> Result<(), ShoobyError> {
+        Ok(())
+    }
+
+    /// Called instead of commit_batch when a save failed, the held saves should be dropped
+    fn abort_batch(&self) {}
+}
+
+/// An enum that can be held by an Enum field.
+/// The field stores the discriminant, so it must be stable between firmware versions if persistent
+pub trait ShoobyEnum: Sized {
+    /// The discriminant and name of every variant
+    const VARIANTS: &'static [(u32, &'static str)];
+
+    fn to_discriminant(&self) -> u32;
+    fn from_discriminant(discriminant: u32) -> Option<Self>;
 }
@@ -1,15 +1,62 @@
+use crate::errors::ShoobyError;
+
 pub(crate) unsafe fn any_as_u8_slice<T: Sized>(p: &T) -> &[u8] {
-    ::std::slice::from_raw_parts((p as *const T) as *const u8, ::std::mem::size_of::<T>())
+    ::core::slice::from_raw_parts((p as *const T) as *const u8, ::core::mem::size_of::<T>())
 }
 
 pub(crate) unsafe fn u8_slice_as_any<T: Sized>(p: &[u8]) -> &T {
     &*(p.as_ptr() as *const T)
 }
 
-pub(crate) fn str_from_u8_nul_utf8(utf8_src: &[u8]) -> Result<&str, std::str::Utf8Error> {
+pub(crate) fn str_from_u8_nul_utf8(utf8_src: &[u8]) -> Result<&str, core::str::Utf8Error> {
     let nul_range_end = utf8_src
         .iter()
         .position(|&c| c == b'\0')
         .unwrap_or(utf8_src.len()); // default to length if no `\0` present
-    ::std::str::from_utf8(&utf8_src[0..nul_range_end])
+    ::core::str::from_utf8(&utf8_src[0..nul_range_end])
+}
+
+/// Decodes hex text, two digits per byte, to fill out exactly
+pub(crate) fn hex_decode(text: &str, out: &mut [u8]) -> Result<(), ShoobyError> {
+    if text.len() != out.len() * 2 {
+        return Err(ShoobyError::InvalidSize);
+    }
+
+    let digit = |c: u8| {
+        (c as char)
+            .to_digit(16)
+            .ok_or(ShoobyError::InvalidTypeConversion)
+    };
+    for (byte, digits) in out.iter_mut().zip(text.as_bytes().chunks(2)) {
+        *byte = (digit(digits[0])? << 4 | digit(digits[1])?) as u8;
+    }
+    Ok(())
+}
+
+pub(crate) const FNV_OFFSET_BASIS: u32 = 0x811c_9dc5;
+const FNV_PRIME: u32 = 0x0100_0193;
+
+/// 32 bit FNV-1a hash, can be chained by passing the previous hash
+pub(crate) const fn fnv1a(mut hash: u32, bytes: &[u8]) -> u32 {
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u32;
+        hash = hash.wrapping_mul(FNV_PRIME);
+        i += 1;
+    }
+    hash
+}
+
+pub(crate) const CRC32_INIT: u32 = 0xffff_ffff;
+
+/// CRC-32 (IEEE 802.3), start with CRC32_INIT and invert the result after the last update
+pub(crate) fn crc32_update(mut crc: u32, bytes: &[u8]) -> u32 {
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    crc
 }
@@ -0,0 +1,210 @@
+//! The fields of a DB as they are given to write_with and try_write_with.
+//!
+//! Reading works as on the fields of reader, through indexing by ID. Writing goes through field_mut,
+//! whose setters ask the interceptor of the DB before a field gets a new value:
+//!     `db.write_with(|writer| { writer.field_mut(NAME::ID::SSID).set_string("home").unwrap(); });`
+//!
+//! The first change of a field copies its previous value to the staging buffer, at the offset of the field
+//! in ID order. The DB rolls back and notifies the observer from there, so only the touched fields are copied.
+//! A writer dropped before the DB finished it, when the closure panics, rolls the changed fields back itself.
+
+use core::ops::Deref;
+
+use crate::errors::ShoobyError;
+use crate::shooby_field::{BeforeChange, ShoobyField, ShoobyValue};
+use crate::traits::{ShoobyEnum, ShoobyId, ShoobyInterceptor};
+
+/// The fields of a DB while it is written
+pub struct ShoobyWriter<'a, ID: AsRef<str> + Copy + 'static> {
+    fields: &'a mut [ShoobyField<ID>],
+    staging: &'a mut [u8],
+    interceptor: &'a dyn ShoobyInterceptor<ID = ID>,
+}
+
+impl<'a, ID: ShoobyId> ShoobyWriter<'a, ID> {
+    /// Used by the DB generated by shooby_db!, staging fits the values of all fields
+    #[doc(hidden)]
+    pub fn new(
+        fields: &'a mut [ShoobyField<ID>],
+        staging: &'a mut [u8],
+        interceptor: &'a dyn ShoobyInterceptor<ID = ID>,
+    ) -> Self {
+        // the flags mark the fields already staged, an observer that panicked may have left them set
+        for field in fields.iter_mut() {
+            field.has_changed = false;
+        }
+        Self {
+            fields,
+            staging,
+            interceptor,
+        }
+    }
+
+    /// Used by the DB generated by shooby_db! once the closure returned, it keeps the changes
+    #[doc(hidden)]
+    pub fn finish(self) {
+        core::mem::forget(self);
+    }
+
+    /// The field of id to write to
+    pub fn field_mut(&mut self, id: ID) -> FieldWriter<'_, ID> {
+        let index = id.index();
+        let offset: usize = self.fields[..index].iter().map(|f| f.data_len()).sum();
+        let field = &mut self.fields[index];
+        let len = field.data_len();
+        FieldWriter {
+            field,
+            hook: WriteHook {
+                stage: &mut self.staging[offset..offset + len],
+                interceptor: self.interceptor,
+            },
+        }
+    }
+
+    /// All the fields to write to, in ID order
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = FieldWriter<'_, ID>> {
+        let mut staging = &mut *self.staging;
+        let interceptor = self.interceptor;
+        self.fields.iter_mut().map(move |field| {
+            let (stage, rest) = core::mem::take(&mut staging).split_at_mut(field.data_len());
+            staging = rest;
+            FieldWriter {
+                field,
+                hook: WriteHook { stage, interceptor },
+            }
+        })
+    }
+
+    /// The field named by its short (`SSID`) or qualified (`NAME::ID::SSID`) name to write to
+    pub fn field_by_name_mut(&mut self, name: &str) -> Option<FieldWriter<'_, ID>> {
+        Some(self.field_mut(ID::from_name(name)?))
+    }
+}
+
+impl<ID: AsRef<str> + Copy> Drop for ShoobyWriter<'_, ID> {
+    /// Reached without finish only while unwinding from the closure, so the write never happened
+    fn drop(&mut self) {
+        restore_staged(self.fields, self.staging);
+        for field in self.fields.iter_mut() {
+            field.has_changed = false;
+        }
+    }
+}
+
+impl<ID: AsRef<str> + Copy> Deref for ShoobyWriter<'_, ID> {
+    type Target = [ShoobyField<ID>];
+
+    fn deref(&self) -> &Self::Target {
+        self.fields
+    }
+}
+
+/// Used by the DB generated by shooby_db!, gives the changed fields back their staged values.
+/// The changed flags are left as they are
+#[doc(hidden)]
+pub fn restore_staged<ID: AsRef<str> + Copy>(fields: &mut [ShoobyField<ID>], staging: &[u8]) {
+    let mut offset = 0;
+    for field in fields.iter_mut() {
+        let value = &staging[offset..];
+        offset += if field.has_changed {
+            field.restore(value)
+        } else {
+            field.data_len()
+        };
+    }
+}
+
+/// A field of a DB while it is written, with the setters of ShoobyField.
+/// The interceptor of the DB is asked before the field gets a new value
+pub struct FieldWriter<'a, ID: 'static> {
+    field: &'a mut ShoobyField<ID>,
+    hook: WriteHook<'a, ID>,
+}
+
+impl<ID: AsRef<str> + Copy> FieldWriter<'_, ID> {
+    pub fn set_num<T: TryInto<i32>>(&mut self, value: T) -> Result<i32, ShoobyError> {
+        self.field.set_num_with(value, &mut self.hook)
+    }
+
+    pub fn set_u32<T: TryInto<u32>>(&mut self, value: T) -> Result<u32, ShoobyError> {
+        self.field.set_u32_with(value, &mut self.hook)
+    }
+
+    pub fn set_i64<T: TryInto<i64>>(&mut self, value: T) -> Result<i64, ShoobyError> {
+        self.field.set_i64_with(value, &mut self.hook)
+    }
+
+    pub fn set_u64<T: TryInto<u64>>(&mut self, value: T) -> Result<u64, ShoobyError> {
+        self.field.set_u64_with(value, &mut self.hook)
+    }
+
+    /// Sets a float value, NaN is never accepted
+    pub fn set_float(&mut self, value: f32) -> Result<f32, ShoobyError> {
+        self.field.set_float_with(value, &mut self.hook)
+    }
+
+    pub fn set_enum<E: ShoobyEnum>(&mut self, value: E) -> Result<E, ShoobyError> {
+        self.field.set_enum_with(value, &mut self.hook)
+    }
+
+    /// Sets an Enum field to the variant with the discriminant, OutOfBounds if there is none
+    #[cfg(feature = "serde")]
+    pub(crate) fn set_discriminant(&mut self, value: u32) -> Result<(), ShoobyError> {
+        self.field.set_discriminant_with(value, &mut self.hook)
+    }
+
+    pub fn set_bool(&mut self, value: bool) -> Result<bool, ShoobyError> {
+        self.field.set_bool_with(value, &mut self.hook)
+    }
+
+    pub fn set_string(&mut self, value: &str) -> Result<(), ShoobyError> {
+        self.field.set_string_with(value, &mut self.hook)
+    }
+
+    pub fn set_blob<T: Sized>(&mut self, value: &T) -> Result<(), ShoobyError> {
+        self.field.set_blob_with(value, &mut self.hook)
+    }
+
+    /// Sets a Blob field from its bytes, InvalidSize if their number is not the blob size
+    pub fn set_blob_bytes(&mut self, value: &[u8]) -> Result<(), ShoobyError> {
+        self.field.set_blob_bytes_with(value, &mut self.hook)
+    }
+
+    /// Same as ShoobyField::set_from_str
+    pub fn set_from_str(&mut self, text: &str) -> Result<(), ShoobyError> {
+        self.field.set_from_str_with(text, &mut self.hook)
+    }
+
+    /// Sets the field from value bytes in the persisted format, with the same checks as the typed setters
+    pub(crate) fn set_from_bytes(&mut self, bytes: &[u8]) -> Result<(), ShoobyError> {
+        self.field.set_from_bytes_with(bytes, &mut self.hook)
+    }
+}
+
+impl<ID> Deref for FieldWriter<'_, ID> {
+    type Target = ShoobyField<ID>;
+
+    fn deref(&self) -> &Self::Target {
+        self.field
+    }
+}
+
+/// Asks the interceptor of the DB before a field is changed, and stages the previous value on its first change
+struct WriteHook<'a, ID: 'static> {
+    stage: &'a mut [u8],
+    interceptor: &'a dyn ShoobyInterceptor<ID = ID>,
+}
+
+impl<ID: AsRef<str> + Copy> BeforeChange<ID> for WriteHook<'_, ID> {
+    fn before_change(
+        &mut self,
+        field: &ShoobyField<ID>,
+        new: &ShoobyValue<'_>,
+    ) -> Result<(), ShoobyError> {
+        self.interceptor.before_write(field, new)?;
+        if !field.has_changed {
+            field.backup(self.stage);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+//! Makes sure the crate keeps building without the `std` feature.
+//! The check runs for the host and, when its standard library is installed, for a cortex-m4f target.
+
+use std::path::Path;
+use std::process::Command;
+
+const EMBEDDED_TARGET: &str = "thumbv7em-none-eabihf";
+
+fn check_no_default_features(target: Option<&str>) {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let target_dir = Path::new(manifest_dir).join("target").join("no_std");
+
+    let mut cmd = Command::new(env!("CARGO"));
+    cmd.current_dir(manifest_dir)
+        .args(["check", "--lib", "--no-default-features", "--target-dir"])
+        .arg(&target_dir);
+
+    if let Some(target) = target {
+        cmd.args(["--target", target]);
+    }
+
+    let output = cmd.output().expect("failed to run cargo");
+    assert!(
+        output.status.success(),
+        "no_std build failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+fn target_installed(target: &str) -> bool {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let output = match Command::new(rustc).args(["--print", "sysroot"]).output() {
+        Ok(output) => output,
+        Err(_) => return false,
+    };
+
+    let sysroot = String::from_utf8_lossy(&output.stdout);
+    Path::new(sysroot.trim())
+        .join("lib")
+        .join("rustlib")
+        .join(target)
+        .exists()
+}
+
+#[test]
+fn builds_without_std_on_host() {
+    check_no_default_features(None);
+}
+
+#[test]
+fn builds_without_std_on_embedded_target() {
+    if !target_installed(EMBEDDED_TARGET) {
+        eprintln!("{} is not installed, skipping", EMBEDDED_TARGET);
+        return;
+    }
+
+    check_no_default_features(Some(EMBEDDED_TARGET));
+}